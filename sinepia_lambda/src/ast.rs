@@ -8,8 +8,27 @@ pub enum Statement {
     Axiom(Axiom),
     /// A theorem.
     TH(Theorem),
+    /// A theorem together with its proof.
+    THPF(ProvedTheorem),
     /// Proof.
     PF(Proof),
+    /// Replaces the proof of an already proven theorem.
+    Reprove(Proof),
+    /// Proof constructed by running a tactic.
+    By(TacticProof),
+    /// Record type declaration.
+    Record(Record),
+    /// Type class declaration, a class is a record whose values are found
+    /// by instance resolution.
+    Class(Record),
+    /// Instance of a type class.
+    Instance(Instance),
+    /// Registers an axiom or theorem as a coercion.
+    Coercion(Variable),
+    /// Transparent definition.
+    Def(Def),
+    /// Changes the reducibility of a definition or proven theorem.
+    Attribute(Attribute),
 }
 
 #[derive(Debug)]
@@ -36,6 +55,93 @@ pub struct Proof {
     pub value: Expr,
 }
 
+#[derive(Debug)]
+pub struct Record {
+    /// The name of the record type.
+    pub name: Variable,
+    /// Parameters of the record type, the record type itself is a function
+    /// from those parameters to `sort`.
+    pub params: Vec<(Variable, Expr)>,
+    /// The universe the record type lives in.
+    pub sort: Universe,
+    /// Named fields, the type of a field may mention parameters and
+    /// fields declared before it.
+    pub fields: Vec<(Variable, Expr)>,
+}
+
+#[derive(Debug)]
+pub struct ProvedTheorem {
+    /// The name of the theorem.
+    pub name: Variable,
+    /// The data type representing the body of the theorem.
+    pub typ: Expr,
+    /// The Theorem's proof.
+    pub value: Expr,
+}
+
+#[derive(Debug)]
+pub struct Def {
+    /// The name of the definition.
+    pub name: Variable,
+    /// The type of the definition, inferred from the body if not given.
+    pub typ: Option<Expr>,
+    /// The body of the definition.
+    pub value: Expr,
+    /// When the definition is unfolded, [Reducibility::Reducible] unless
+    /// stated otherwise.
+    pub reducibility: Reducibility,
+}
+
+/// Controls when [Normalize](crate::Normalize) unfolds a declaration that has
+/// a value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reducibility {
+    /// Always unfolded, the default for definitions.
+    Reducible,
+    /// Only unfolded by the conversion check as a last resort, when the two
+    /// sides are not equal otherwise.
+    Irreducible,
+    /// Never unfolded, the default for theorems.
+    Opaque,
+}
+
+#[derive(Debug)]
+pub struct Attribute {
+    /// The name of the definition or theorem.
+    pub name: Variable,
+    /// Its new reducibility.
+    pub reducibility: Reducibility,
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    /// The name of the instance.
+    pub name: Variable,
+    /// The type of the instance, it must end in a class applied to its
+    /// parameters, possibly after instance implicit binders.
+    pub typ: Expr,
+    /// The value of the instance.
+    pub value: Expr,
+}
+
+#[derive(Debug)]
+pub struct TacticProof {
+    /// The name of the theorem.
+    pub name: Variable,
+    /// The tactic that constructs the theorem's proof.
+    pub tactic: Tactic,
+}
+
+#[derive(Debug)]
+pub enum Tactic {
+    /// Proof by reflection using an instance of [DECIDABLE], see
+    /// [Tactic::run].
+    Decide,
+    /// Backward proof search using the entries of the context, with an
+    /// optional search depth that defaults to [AUTO_DEPTH](crate::AUTO_DEPTH).
+    Auto(Option<Uinf>),
+}
+
 #[derive(Clone, Debug)]
 pub enum Expr {
     Var(Variable),
@@ -43,6 +149,11 @@ pub enum Expr {
     Pi(Pi),
     Lambda(Lambda),
     App(Application),
+    Quot(Quot),
+    Rec(Rec),
+    Proj(Proj),
+    Sigma(Sigma),
+    Pair(Pair),
 }
 
 #[derive(PartialEq, Eq, Clone, Hash, Debug)]
@@ -53,6 +164,16 @@ pub enum Variable {
     GenSym(GenSym),
 }
 
+impl Variable {
+    /// Returns `self.field`, the name of a declaration living in the
+    /// namespace of `self`.
+    pub fn qualify(&self, field: &Variable) -> Variable {
+        Variable::Str(StrVar {
+            name: format!("{}.{}", self, field),
+        })
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Hash, Debug)]
 pub struct StrVar {
     pub name: String,
@@ -68,9 +189,14 @@ pub struct GenSym {
 ///
 /// we are only limited to
 /// 340282366920938463463374607431768211455 nested universe :(
+///
+/// [Universe::Prop] is ordered below every [Universe::Type].
 #[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug)]
-pub struct Universe {
-    pub level: Uinf,
+pub enum Universe {
+    /// The impredicative sort of propositions, `Prop: U₀`.
+    Prop,
+    /// The predicative universe `Uᵢ`.
+    Type(Uinf),
 }
 
 /// Function Application
@@ -80,9 +206,101 @@ pub struct Application {
     pub e2: Box<Expr>,
 }
 
+/// Quotient types and their primitive operations.
+///
+/// All of them are fully applied, see [TypeInfer](crate::TypeInfer) for their typing rules.
+#[derive(Clone, Debug)]
+pub enum Quot {
+    /// The quotient type `(A / R)` of `A` by the relation `R`.
+    Type { a: Box<Expr>, r: Box<Expr> },
+    /// `quot_mk R e` is the class of `e` in the quotient by `R`.
+    Mk { r: Box<Expr>, e: Box<Expr> },
+    /// `quot_lift f h q` applies `f` to the representative of `q`, `h` proves
+    /// that `f` respects the relation.
+    Lift {
+        f: Box<Expr>,
+        h: Box<Expr>,
+        q: Box<Expr>,
+    },
+    /// `quot_sound R a b h` proves that the classes of `a` and `b` are equal
+    /// given `h: R a b`.
+    Sound {
+        r: Box<Expr>,
+        a: Box<Expr>,
+        b: Box<Expr>,
+        h: Box<Expr>,
+    },
+}
+
+impl Quot {
+    /// Subexpressions in the order they are written.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Quot::Type { a, r } => vec![a, r],
+            Quot::Mk { r, e } => vec![r, e],
+            Quot::Lift { f, h, q } => vec![f, h, q],
+            Quot::Sound { r, a, b, h } => vec![r, a, b, h],
+        }
+    }
+    /// Mutable subexpressions in the order they are written.
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Quot::Type { a, r } => vec![a, r],
+            Quot::Mk { r, e } => vec![r, e],
+            Quot::Lift { f, h, q } => vec![f, h, q],
+            Quot::Sound { r, a, b, h } => vec![r, a, b, h],
+        }
+    }
+}
+
+/// Name under which [Quot::Sound] is recorded as a dependency. It is a
+/// keyword, so no declaration can have this name.
+pub const QUOT_SOUND: &str = "quot_sound";
+
+/// Name of the class [Tactic::Decide] resolves an instance of. It takes the
+/// proposition as its only parameter and has the fields [HOLDS] and [SOUND].
+pub const DECIDABLE: &str = "Decidable";
+/// Field of [DECIDABLE] with the Church encoded boolean that decides the
+/// proposition.
+pub const HOLDS: &str = "holds";
+/// Field of [DECIDABLE] that proves the proposition when [HOLDS] is `true`.
+pub const SOUND: &str = "sound";
+
+/// Record construction `{ x := a, y := b }`.
+///
+/// The record type is inferred from the set of field names.
+#[derive(Clone, Debug)]
+pub struct Rec {
+    pub fields: Vec<(Variable, Expr)>,
+}
+
+/// Record projection `e.field`.
+///
+/// If `e` is the name of a record type `R` then `R.field` refers to the
+/// projection function generated for `field`. Dependent pairs are projected
+/// with the fields [FST] and [SND].
+#[derive(Clone, Debug)]
+pub struct Proj {
+    pub e: Box<Expr>,
+    pub field: Variable,
+}
+
+/// Name of the first projection of a dependent pair.
+pub const FST: &str = "fst";
+/// Name of the second projection of a dependent pair.
+pub const SND: &str = "snd";
+
+/// Dependent pair `pair T a b` where `T` is the [Sigma] type of the pair.
+#[derive(Clone, Debug)]
+pub struct Pair {
+    pub typ: Box<Expr>,
+    pub fst: Box<Expr>,
+    pub snd: Box<Expr>,
+}
+
 /// Abstraction
 ///
-/// Depending on `T` this maybe a [Lambda] expression of [Pi] expresion.
+/// Depending on `T` this maybe a [Lambda] expression, [Pi] expresion or a [Sigma] type.
 #[derive(Clone, Debug)]
 pub struct Abstraction<T> {
     /// Bound variable
@@ -91,6 +309,9 @@ pub struct Abstraction<T> {
     pub t: Box<Expr>,
     /// Expression containing the bound variable
     pub e: Box<Expr>,
+    /// [true] for instance implicit binders `[x: A]`, the argument for
+    /// those is found by instance resolution while elaborating.
+    pub inst: bool,
     /// Phantom type: This is to differentiate between Pi and lambdas
     pub _ty: PhantomData<T>,
 }
@@ -101,6 +322,9 @@ pub struct PiPhantom;
 #[derive(Clone, Debug)]
 pub struct LambdaPhantom;
 
+#[derive(Clone, Debug)]
+pub struct SigmaPhantom;
+
 /// Pi types
 pub type Pi = Abstraction<PiPhantom>;
 impl From<Pi> for Expr {
@@ -115,3 +339,12 @@ impl From<Lambda> for Expr {
         Expr::Lambda(l)
     }
 }
+/// Sigma types
+///
+/// The subset type `{ x: A | P }` is the Sigma type `Σ x: A, P`.
+pub type Sigma = Abstraction<SigmaPhantom>;
+impl From<Sigma> for Expr {
+    fn from(l: Sigma) -> Self {
+        Expr::Sigma(l)
+    }
+}
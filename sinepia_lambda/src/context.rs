@@ -1,5 +1,14 @@
-use crate::{ast::*, Error, Normalize, Subst, SubstTracker, TypeInfer};
-use std::{collections::HashMap, mem};
+use crate::decl::{Declaration, Kind, Location, Metadata};
+use crate::elab::{Elaborated, Elaborator};
+use crate::pretty::Pretty;
+use crate::subst::subst_all;
+use crate::unify::mentions;
+use crate::{ast::*, search, tactic, Error, Normalize, Subst, SubstTracker, TypeInfer};
+use im_rc::Vector;
+use std::collections::{HashMap, HashSet};
+use std::iter::successors;
+use std::rc::Rc;
+use std::{marker::PhantomData, mem, mem::discriminant};
 
 #[derive(Clone)]
 struct AxiomEntry {
@@ -10,6 +19,14 @@ struct AxiomEntry {
 struct ProvedTheoremEntry {
     typ: Expr,
     val: Expr,
+    reducibility: Reducibility,
+}
+
+#[derive(Clone)]
+struct DefEntry {
+    typ: Expr,
+    val: Expr,
+    reducibility: Reducibility,
 }
 
 #[derive(Clone)]
@@ -17,17 +34,117 @@ struct UnprovedTheoremEntry {
     typ: Expr,
 }
 
+/// A record type `R (x₁: T₁) ... (xₙ: Tₙ): U { f₁: A₁, ... fₘ: Aₘ }`.
+#[derive(Clone)]
+pub(crate) struct RecordEntry {
+    /// `Π x₁: T₁, ... Π xₙ: Tₙ, U`
+    typ: Expr,
+    pub(crate) params: Vec<(Variable, Expr)>,
+    pub(crate) fields: Vec<(Variable, Expr)>,
+    /// [true] if the record was declared as a type class.
+    class: bool,
+}
+
+impl RecordEntry {
+    /// Returns `Aᵢ` where the parameters are replaced with `args` and the
+    /// fields before `fᵢ` are replaced with `earlier`.
+    pub(crate) fn field_type(
+        &self,
+        i: usize,
+        args: &[Expr],
+        earlier: &[Expr],
+        trk: &mut SubstTracker,
+    ) -> Expr {
+        let pairs: Vec<(Variable, Expr)> = self
+            .params
+            .iter()
+            .map(|(x, _)| x.clone())
+            .zip(args.iter().cloned())
+            .chain(
+                self.fields[..i]
+                    .iter()
+                    .map(|(f, _)| f.clone())
+                    .zip(earlier.iter().cloned()),
+            )
+            .collect();
+        let mut typ = self.fields[i].1.clone();
+        subst_all(&mut typ, &pairs, trk);
+        typ
+    }
+}
+
 #[derive(Clone)]
 enum Entry {
     Axiom(AxiomEntry),
     Proved(ProvedTheoremEntry),
     Unproved(UnprovedTheoremEntry),
+    Record(RecordEntry),
+    Def(DefEntry),
 }
 
 /// The `Γ` in `Γ |- x: A`
+///
+/// It is made of a global environment of declarations (axioms, theorems and
+/// record types) and a local telescope of the variables bound by the
+/// binders we are under. A bound variable shadows any declaration with the
+/// same name, and earlier bound variables with the same name.
+///
+/// Declarations are kept in the order they were made together with their
+/// [Metadata].
+///
+/// Cloning is cheap: the global environment is shared until a clone adds a
+/// declaration, which then only copies what changes, and binding a variable
+/// shares the rest of the telescope.
 #[derive(Clone)]
 pub struct Context {
-    ctx: HashMap<Variable, Entry>,
+    /// Shared between clones and only copied when a clone adds to it.
+    env: Rc<Env>,
+    locals: Locals,
+    /// Whether [Reducibility::Irreducible] declarations are unfolded.
+    unfold_irreducible: bool,
+    /// A declaration treated as if it did not exist, see [Context::hiding].
+    hidden: Option<Variable>,
+}
+
+/// The global environment of a [Context]. Its collections are persistent
+/// so that copying it for a [Checkpoint] or a clone of the [Context] that
+/// is then changed shares everything that is left unchanged.
+#[derive(Clone, Default)]
+struct Env {
+    entries: im_rc::HashMap<Variable, Entry>,
+    /// Names of the declarations in the order they were made.
+    order: Vector<Variable>,
+    meta: im_rc::HashMap<Variable, Metadata>,
+    /// Instances of type classes in the order they were declared.
+    instances: Vector<Variable>,
+    /// Coercions in the order they were declared together with the number
+    /// of arguments they take, the last one being the coerced value.
+    coercions: Vector<(Variable, usize)>,
+    /// The entries mentioned by the type, proof or fields of every entry.
+    deps: im_rc::HashMap<Variable, HashSet<Variable>>,
+}
+
+/// The declarations of a [Context] at some point, see [Context::checkpoint].
+#[derive(Clone)]
+pub struct Checkpoint {
+    env: Rc<Env>,
+}
+
+/// Bound variables and their types as a persistent list, innermost first,
+/// so binding a variable shares the rest of the telescope.
+#[derive(Clone, Default)]
+struct Locals(Option<Rc<Local>>);
+
+struct Local {
+    x: Variable,
+    t: Expr,
+    outer: Locals,
+}
+
+impl Locals {
+    fn iter(&self) -> impl Iterator<Item = &Local> {
+        successors(self.0.as_deref(), |l| l.outer.0.as_deref())
+    }
 }
 
 impl Default for Context {
@@ -40,87 +157,666 @@ impl Context {
     /// Returns a new context
     pub fn new() -> Self {
         Context {
-            ctx: Default::default(),
+            env: Default::default(),
+            locals: Default::default(),
+            unfold_irreducible: false,
+            hidden: None,
+        }
+    }
+    /// Saves the declarations of this [Context] so that they can be restored
+    /// by [Context::rollback].
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            env: self.env.clone(),
+        }
+    }
+    /// Undoes every declaration, proof and attribute added since `cp` was
+    /// taken.
+    pub fn rollback(&mut self, cp: Checkpoint) {
+        self.env = cp.env;
+    }
+    /// Returns [true] if anything was added to this [Context] since `cp` was
+    /// taken.
+    pub fn changed_since(&self, cp: &Checkpoint) -> bool {
+        !Rc::ptr_eq(&self.env, &cp.env)
+    }
+    /// Iterates over the declarations made since `cp` was taken in the order
+    /// they were made.
+    pub fn declared_since<'a>(
+        &'a self,
+        cp: &'a Checkpoint,
+    ) -> impl Iterator<Item = Declaration<'a>> {
+        self.env
+            .order
+            .iter()
+            .filter(|v| !cp.env.entries.contains_key(*v))
+            .filter_map(|v| self.declaration(v))
+    }
+    /// Runs `f` on this [Context] and undoes everything it added if it fails.
+    pub fn transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Context) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let cp = self.checkpoint();
+        let result = f(self);
+        if result.is_err() {
+            self.rollback(cp);
+        }
+        result
+    }
+    /// The global environment, copied first if it is shared with a clone.
+    fn env_mut(&mut self) -> &mut Env {
+        Rc::make_mut(&mut self.env)
+    }
+    /// Adds the new declaration `v`.
+    fn declare(&mut self, v: Variable, entry: Entry, kind: Kind) {
+        let env = self.env_mut();
+        env.order.push_back(v.clone());
+        env.meta.insert(
+            v.clone(),
+            Metadata {
+                kind,
+                location: None,
+                doc: None,
+            },
+        );
+        env.entries.insert(v, entry);
+    }
+    /// Returns the declaration `v` unless it does not exist or is shadowed by
+    /// a bound variable.
+    pub fn declaration(&self, v: &Variable) -> Option<Declaration<'_>> {
+        let (typ, value, reducibility) = match self.global(v)? {
+            Entry::Axiom(a) => (&a.typ, None, None),
+            Entry::Proved(p) => (&p.typ, Some(&p.val), Some(p.reducibility)),
+            Entry::Unproved(u) => (&u.typ, None, None),
+            Entry::Record(r) => (&r.typ, None, None),
+            Entry::Def(d) => (&d.typ, Some(&d.val), Some(d.reducibility)),
+        };
+        let (name, meta) = self.env.meta.get_key_value(v)?;
+        Some(Declaration {
+            name,
+            typ,
+            value,
+            meta,
+            reducibility,
+            coercion: self.coercion_arity(v).is_some(),
+        })
+    }
+    /// Iterates over the declarations in the order they were made.
+    pub fn declarations(&self) -> impl Iterator<Item = Declaration<'_>> {
+        self.env.order.iter().filter_map(|v| self.declaration(v))
+    }
+    /// Iterates over the axioms in the order they were declared.
+    pub fn axioms(&self) -> impl Iterator<Item = Declaration<'_>> {
+        self.declarations().filter(|d| d.meta.kind == Kind::Axiom)
+    }
+    /// Iterates over the theorems, proven or not, in the order they were
+    /// declared.
+    pub fn theorems(&self) -> impl Iterator<Item = Declaration<'_>> {
+        self.declarations().filter(|d| d.meta.kind == Kind::Theorem)
+    }
+    /// Iterates over the definitions in the order they were declared.
+    pub fn definitions(&self) -> impl Iterator<Item = Declaration<'_>> {
+        self.declarations()
+            .filter(|d| d.meta.kind == Kind::Definition)
+    }
+    /// Writes the declarations in the order they were made using the syntax
+    /// of the REPL, so that replaying the output rebuilds this [Context].
+    /// Projections are left out since replaying their record generates them.
+    /// The proof of a theorem is written after its statement and everything
+    /// the proof mentions, since it may use declarations made after the
+    /// theorem.
+    pub fn serialize(&self) -> String {
+        let position: HashMap<&Variable, usize> = self
+            .env
+            .order
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (v, i))
+            .collect();
+        let mut proofs: HashMap<usize, String> = HashMap::new();
+        let mut out = String::new();
+        for (i, v) in self.env.order.iter().enumerate() {
+            match self.declaration(v) {
+                Some(d) if d.meta.kind != Kind::Projection => {
+                    out += &self.serialize_declaration(&d, &position, &mut proofs)
+                }
+                _ => {}
+            }
+            if let Some(proof) = proofs.remove(&i) {
+                out += &proof;
+            }
+        }
+        out
+    }
+    /// Writes `d` for [Context::serialize]. The proof of a theorem and its
+    /// attributes go to `proofs` under the position they have to follow.
+    fn serialize_declaration(
+        &self,
+        d: &Declaration,
+        position: &HashMap<&Variable, usize>,
+        proofs: &mut HashMap<usize, String>,
+    ) -> String {
+        let mut out = String::new();
+        if let Some(doc) = &d.meta.doc {
+            for line in doc.lines() {
+                out += &format!("/// {}\n", line);
+            }
+        }
+        let (name, typ) = (d.name, d.typ);
+        match (d.meta.kind, d.value) {
+            (Kind::Axiom, _) => out += &format!("axiom {} : {}\n", name, typ),
+            (Kind::Theorem, None) => out += &format!("theorem {} : {}\n", name, typ),
+            (Kind::Theorem, Some(val)) => {
+                out += &format!("theorem {} : {}\n", name, typ);
+                let after = self
+                    .env
+                    .deps
+                    .get(name)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|u| position.get(u))
+                    .chain(position.get(name))
+                    .max()
+                    .unwrap();
+                let proof = proofs.entry(*after).or_default();
+                *proof += &format!("{} = {}\n", name, val);
+                if let Some(r) = d.reducibility.filter(|r| *r != Reducibility::Opaque) {
+                    *proof += &format!("attribute {} {}\n", r, name);
+                }
+            }
+            (Kind::Record | Kind::Class, _) => {
+                let r = self.lookup_record(name).unwrap();
+                let mut sort = typ;
+                while let Expr::Pi(p) = sort {
+                    sort = &p.e;
+                }
+                out += &format!("{} {}", d.meta.kind, name);
+                for (x, t) in &r.params {
+                    out += &format!(" ({} : {})", x, t);
+                }
+                out += &format!(" : {} {{", sort);
+                for (i, (f, t)) in r.fields.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    out += &format!("{}{} : {}", sep, f, t);
+                }
+                out += " }\n";
+            }
+            (Kind::Instance, Some(val)) => {
+                out += &format!("instance {} : {} = {}\n", name, typ, val)
+            }
+            (_, val) => {
+                if let Some(r) = d.reducibility.filter(|r| *r != Reducibility::Reducible) {
+                    out += &format!("{} ", r);
+                }
+                out += &format!("def {} : {} = {}\n", name, typ, val.unwrap());
+            }
+        }
+        let default = match d.meta.kind {
+            Kind::Instance => Reducibility::Reducible,
+            // Definitions carry it as a prefix and theorems with their proof.
+            _ => d.reducibility.unwrap_or(Reducibility::Reducible),
+        };
+        if let Some(r) = d.reducibility.filter(|r| *r != default) {
+            out += &format!("attribute {} {}\n", r, name);
+        }
+        if d.coercion {
+            out += &format!("coercion {}\n", name);
         }
+        out
+    }
+    /// Records where the declaration `v` was written.
+    pub fn set_location(&mut self, v: &Variable, location: Location) -> Result<(), Error> {
+        let meta = self
+            .env_mut()
+            .meta
+            .get_mut(v)
+            .ok_or(Error::VariableNotFound)?;
+        meta.location = Some(location);
+        Ok(())
+    }
+    /// Attaches the documentation `doc` to the declaration `v`.
+    pub fn set_doc(&mut self, v: &Variable, doc: String) -> Result<(), Error> {
+        let meta = self
+            .env_mut()
+            .meta
+            .get_mut(v)
+            .ok_or(Error::VariableNotFound)?;
+        meta.doc = Some(doc);
+        Ok(())
     }
-    /// Returns [true] if there is an axiom or theorem with
-    /// name `v` and [false] otherwise.
+    /// Returns the declaration named `v` unless it is shadowed by a bound
+    /// variable or hidden.
+    fn global(&self, v: &Variable) -> Option<&Entry> {
+        if self.is_local(v) || self.hidden.as_ref() == Some(v) {
+            None
+        } else {
+            self.env.entries.get(v)
+        }
+    }
+    /// Returns [true] if there is an axiom, theorem or record type with
+    /// name `v` that is not shadowed by a bound variable and [false] otherwise.
     pub fn contains(&self, v: &Variable) -> bool {
-        self.ctx.contains_key(v)
+        self.global(v).is_some()
+    }
+    /// Returns [true] if `v` is a bound variable and [false] otherwise.
+    pub fn is_local(&self, v: &Variable) -> bool {
+        self.locals.iter().any(|l| &l.x == v)
     }
     /// returns [true] if `v` is refers to an axiom, and
     /// [false] otherwise.
     pub fn is_axiom(&self, v: &Variable) -> bool {
-        matches!(self.ctx.get(v), Some(Entry::Axiom(_)))
+        matches!(self.global(v), Some(Entry::Axiom(_)))
     }
     /// returns [true] if `v` refers to a proven theory, and
     /// [false] otherwise.
     pub fn is_proven_theory(&self, v: &Variable) -> bool {
-        matches!(self.ctx.get(v), Some(Entry::Proved(_)))
+        matches!(self.global(v), Some(Entry::Proved(_)))
     }
     /// returns [true] if `v` refers to a unproven theory and
     /// [false] otherwise.
     pub fn is_unproven_theory(&self, v: &Variable) -> bool {
-        matches!(self.ctx.get(v), Some(Entry::Unproved(_)))
+        matches!(self.global(v), Some(Entry::Unproved(_)))
     }
-    /// returns the value stored for `v` assuming it is a proven theory.
-    pub fn lookup_value(&self, v: &Variable) -> Option<&Expr> {
-        self.ctx.get(v).and_then(|e: &Entry| {
-            if let Entry::Proved(p) = e {
-                Some(&p.val)
-            } else {
-                None
+    /// returns [true] if `v` refers to a definition and [false] otherwise.
+    pub fn is_definition(&self, v: &Variable) -> bool {
+        matches!(self.global(v), Some(Entry::Def(_)))
+    }
+    /// returns [true] if `v` refers to a record type and [false] otherwise.
+    pub fn is_record(&self, v: &Variable) -> bool {
+        matches!(self.global(v), Some(Entry::Record(_)))
+    }
+    /// returns [true] if `v` refers to a type class and [false] otherwise.
+    pub fn is_class(&self, v: &Variable) -> bool {
+        matches!(self.global(v), Some(Entry::Record(r)) if r.class)
+    }
+    /// Returns [true] if `typ` is a class applied to its parameters,
+    /// possibly under Π binders (like the type of an instance).
+    pub(crate) fn is_instance_type(&self, typ: &Expr) -> bool {
+        let mut e = typ;
+        while let Expr::Pi(p) = e {
+            e = &p.e;
+        }
+        while let Expr::App(a) = e {
+            e = &a.e1;
+        }
+        matches!(e, Expr::Var(v) if self.is_class(v))
+    }
+    /// Iterates over the name and type of every instance, the most recently
+    /// declared instance first.
+    pub(crate) fn instances(&self) -> impl Iterator<Item = (&Variable, &Expr)> {
+        self.env
+            .instances
+            .iter()
+            .rev()
+            .filter_map(|v| Some((v, self.lookup_global(v)?)))
+    }
+    /// Returns the number of arguments taken by the coercion `v`, the last one
+    /// being the coerced value, or [None] if `v` is not a coercion.
+    pub fn coercion_arity(&self, v: &Variable) -> Option<usize> {
+        self.env
+            .coercions
+            .iter()
+            .find_map(|(c, n)| if c == v { Some(*n) } else { None })
+    }
+    /// Returns a value that displays `e` either as is or with every fully
+    /// applied coercion `(c a₁ ... aₙ x)` shown as just `x`.
+    pub fn pretty<'a>(&'a self, e: &'a Expr, show_coercions: bool) -> Pretty<'a> {
+        Pretty {
+            expr: e,
+            ctx: self,
+            coercions: show_coercions,
+        }
+    }
+    /// Iterates over every coercion and its arity, the most recently declared
+    /// coercion first.
+    pub(crate) fn coercions(&self) -> impl Iterator<Item = (&Variable, usize, &Expr)> {
+        self.env
+            .coercions
+            .iter()
+            .rev()
+            .filter_map(|(v, n)| Some((v, *n, self.lookup_global(v)?)))
+    }
+    /// Returns the entries that the type, proof or fields of `v` mention
+    /// directly sorted by name, or [None] if `v` does not exist.
+    pub fn dependencies(&self, v: &Variable) -> Option<Vec<Variable>> {
+        if !self.contains(v) {
+            return None;
+        }
+        let mut deps: Vec<Variable> = self
+            .env
+            .deps
+            .get(v)
+            .map(|d| d.iter().filter(|d| **d != quot_sound()).cloned().collect())
+            .unwrap_or_default();
+        deps.sort_by_cached_key(|d| d.to_string());
+        Some(deps)
+    }
+    /// Returns [true] if `v` depends on `w`, directly or through other
+    /// entries.
+    pub fn depends_on(&self, v: &Variable, w: &Variable) -> bool {
+        let mut seen = HashSet::new();
+        let mut todo = vec![v];
+        while let Some(u) = todo.pop() {
+            for d in self.env.deps.get(u).into_iter().flatten() {
+                if d == w {
+                    return true;
+                }
+                if seen.insert(d) {
+                    todo.push(d);
+                }
             }
+        }
+        false
+    }
+    /// Returns the entries of the context that occur free in `es`.
+    fn constants<'a>(&self, es: impl IntoIterator<Item = &'a Expr>) -> HashSet<Variable> {
+        let mut found = HashSet::new();
+        for e in es {
+            constants(self, e, &mut vec![], &mut found);
+        }
+        found
+    }
+    /// Returns the declaration of the record type `v` if it exists.
+    pub(crate) fn lookup_record(&self, v: &Variable) -> Option<&RecordEntry> {
+        match self.global(v) {
+            Some(Entry::Record(r)) => Some(r),
+            _ => None,
+        }
+    }
+    /// Iterates over every record type in the context.
+    pub(crate) fn records(&self) -> impl Iterator<Item = (&Variable, &RecordEntry)> {
+        self.env
+            .order
+            .iter()
+            .filter_map(|v| Some((v, self.lookup_record(v)?)))
+    }
+    /// Returns the name of the projection function `R.f` if `p` is `R.f`
+    /// for some record type `R`.
+    pub(crate) fn projection_function(&self, p: &Proj) -> Option<Variable> {
+        match p.e.as_ref() {
+            Expr::Var(r) if self.is_record(r) => Some(r.qualify(&p.field)),
+            _ => None,
+        }
+    }
+    /// returns the value stored for `v` assuming it is a proven theory or
+    /// a definition.
+    pub fn lookup_value(&self, v: &Variable) -> Option<&Expr> {
+        self.global(v).and_then(|e: &Entry| match e {
+            Entry::Proved(p) => Some(&p.val),
+            Entry::Def(d) => Some(&d.val),
+            _ => None,
         })
     }
-    /// Returns the type of `v` whether it is axiom, proven theory
-    /// or unproven theory as long as it exists in the context.
+    /// Returns the reducibility of `v` if it is a proven theory or a definition.
+    pub fn reducibility(&self, v: &Variable) -> Option<Reducibility> {
+        match self.global(v) {
+            Some(Entry::Proved(p)) => Some(p.reducibility),
+            Some(Entry::Def(d)) => Some(d.reducibility),
+            _ => None,
+        }
+    }
+    /// Returns the value `v` unfolds to during normalization if any.
+    pub(crate) fn unfold(&self, v: &Variable) -> Option<&Expr> {
+        match self.reducibility(v)? {
+            Reducibility::Reducible => self.lookup_value(v),
+            Reducibility::Irreducible if self.unfold_irreducible => self.lookup_value(v),
+            _ => None,
+        }
+    }
+    /// Changes the reducibility of the proven theory or definition `v`.
+    pub fn set_reducibility(&mut self, v: &Variable, r: Reducibility) -> Result<(), Error> {
+        if self.is_local(v) {
+            return Err(Error::VariableNotFound);
+        }
+        match self.env_mut().entries.get_mut(v) {
+            Some(Entry::Proved(p)) => p.reducibility = r,
+            Some(Entry::Def(d)) => d.reducibility = r,
+            Some(_) => return Err(Error::NoValue(v.clone())),
+            None => return Err(Error::VariableNotFound),
+        }
+        Ok(())
+    }
+    /// Returns the type of `v` whether it is a bound variable, axiom,
+    /// proven theory, unproven theory or record type as long as it exists in
+    /// the context.
     pub fn lookup_type(&self, v: &Variable) -> Option<&Expr> {
-        self.ctx.get(v).map(|x: &Entry| match x {
+        self.lookup_local(v).or_else(|| self.lookup_global(v))
+    }
+    /// Returns the type of the bound variable `v` if it exists.
+    pub fn lookup_local(&self, v: &Variable) -> Option<&Expr> {
+        self.locals
+            .iter()
+            .find_map(|l| if &l.x == v { Some(&l.t) } else { None })
+    }
+    /// Returns the type of the declaration `v` unless it does not exist or
+    /// is shadowed by a bound variable.
+    pub fn lookup_global(&self, v: &Variable) -> Option<&Expr> {
+        self.global(v).map(|x: &Entry| match x {
             Entry::Axiom(a) => &a.typ,
             Entry::Proved(t) => &t.typ,
             Entry::Unproved(u) => &u.typ,
+            Entry::Record(r) => &r.typ,
+            Entry::Def(d) => &d.typ,
         })
     }
+    /// Iterates over the name and type of every bound variable, innermost
+    /// first, then of every declaration that is not shadowed.
+    pub(crate) fn hypotheses(&self) -> impl Iterator<Item = (&Variable, &Expr)> {
+        let locals = self
+            .locals
+            .iter()
+            .enumerate()
+            .filter(|(i, l)| self.locals.iter().take(*i).all(|m| m.x != l.x))
+            .map(|(_, l)| (&l.x, &l.t));
+        let globals = self
+            .env
+            .order
+            .iter()
+            .filter_map(|v| Some((v, self.lookup_global(v)?)));
+        locals.chain(globals)
+    }
     /// Stores a new value for the unproven theorem represented by `v`.
-    /// Returns the old value if it exists, otherwise [None] is returned.
     /// Error triggering:
-    /// 1- `v` represents an axiom.
-    /// 2- `v` does not exist.
-    /// 2- `expr` does not type check
-    /// 3- type of `exp` and type of `v` does not match.
+    /// 1- `v` represents an axiom, a definition or a record type.
+    /// 2- `v` is already proven, see [Context::reprove].
+    /// 3- `v` does not exist.
+    /// 4- `expr` does not type check
+    /// 5- type of `exp` and type of `v` does not match.
+    /// 6- `expr` mentions `v` or something that depends on `v`.
     pub fn extend_type(
         &mut self,
         v: &Variable,
         expr: Expr,
         trk: &mut SubstTracker,
+    ) -> Result<(), Error> {
+        if self.is_proven_theory(v) {
+            return Err(Error::AlreadyProven);
+        }
+        let deps = self.check_proof(v, &expr, trk)?;
+        self.env_mut().deps.insert(v.clone(), deps);
+        self.extend_type_unchecked(v, expr);
+        Ok(())
+    }
+    /// Replaces the proof of the proven theorem `v` with `expr` and returns
+    /// the old proof if there was one. Every declaration that depends on `v`, directly or
+    /// through others, is checked again against the new proof, if any of
+    /// them no longer type checks nothing changes and
+    /// [Error::BrokenDependents] lists them. Theorems that are not proven yet
+    /// are handled by [Context::extend_type].
+    pub fn reprove(
+        &mut self,
+        v: &Variable,
+        expr: Expr,
+        trk: &mut SubstTracker,
     ) -> Result<Option<Expr>, Error> {
+        if !self.is_proven_theory(v) {
+            return self.extend_type(v, expr, trk).map(|_| None);
+        }
+        let deps = self.check_proof(v, &expr, trk)?;
+        let mut ctx = self.clone();
+        ctx.env_mut().deps.insert(v.clone(), deps);
+        let old = ctx.extend_type_unchecked(v, expr);
+        let broken: Vec<Variable> = self
+            .dependents(v)
+            .into_iter()
+            .filter(|w| !ctx.recheck(w, trk))
+            .collect();
+        if !broken.is_empty() {
+            return Err(Error::BrokenDependents(broken));
+        }
+        *self = ctx;
+        Ok(old)
+    }
+    /// Checks that `expr` is a proof of the theorem `v` and returns the
+    /// entries it depends on.
+    fn check_proof(
+        &self,
+        v: &Variable,
+        expr: &Expr,
+        trk: &mut SubstTracker,
+    ) -> Result<HashSet<Variable>, Error> {
         if self.is_axiom(v) {
             return Err(Error::CannotProveAxiom);
         }
-        let typ = match self.lookup_type(v) {
+        if self.is_record(v) || self.is_definition(v) {
+            return Err(Error::NotATheorem(v.clone()));
+        }
+        let typ = match self.lookup_global(v) {
             Some(typ) => typ,
             None => return Err(Error::VariableNotFound),
         };
-        let typ2 = match expr.type_infer(self.clone(), trk) {
+        let deps = self.constants([typ, expr]);
+        if let Some(d) = deps.iter().find(|d| *d == v || self.depends_on(d, v)) {
+            return Err(Error::CircularProof(d.clone()));
+        }
+        let typ2 = match expr.type_infer(self, trk) {
             Some(typ2) => typ2,
             None => return Err(Error::ExprDoesNotTypeCheck(None)),
         };
         if self.types_equal(trk, typ, &typ2) {
-            Ok(self.extend_type_unchecked(v, expr))
+            Ok(deps)
         } else {
-            Err(Error::TypesDoesNotMatch((typ.clone(), typ2)))
+            Err(Error::TypesDoesNotMatch(Box::new((typ.clone(), typ2))))
         }
     }
+    /// Returns [true] if the declaration `v` still type checks.
+    fn recheck(&self, v: &Variable, trk: &mut SubstTracker) -> bool {
+        let (typ, val) = match self.env.entries.get(v) {
+            Some(Entry::Axiom(a)) => (&a.typ, None),
+            Some(Entry::Unproved(u)) => (&u.typ, None),
+            Some(Entry::Proved(p)) => (&p.typ, Some(&p.val)),
+            Some(Entry::Def(d)) => (&d.typ, Some(&d.val)),
+            Some(Entry::Record(r)) => {
+                let mut local = self.clone();
+                for (x, t) in r.params.iter().chain(&r.fields) {
+                    if local.check_type(t, trk).is_err() {
+                        return false;
+                    }
+                    local = local.with_local(x.clone(), t.clone());
+                }
+                return true;
+            }
+            None => return true,
+        };
+        if self.check_type(typ, trk).is_err() {
+            return false;
+        }
+        match val.map(|val| val.type_infer(self, trk)) {
+            None => true,
+            Some(Some(typ2)) => self.types_equal(trk, typ, &typ2),
+            Some(None) => false,
+        }
+    }
+    /// Returns the axioms and unproven theorems that `v` relies on, directly
+    /// or through other declarations, sorted by name and including `v` itself
+    /// if it is one of them. Unproven theorems are assumed the same way axioms
+    /// are (they are `sorry`), [Context::is_unproven_theory] tells them apart.
+    /// The soundness of quotients is an axiom too, it is listed as
+    /// [QUOT_SOUND] when [Quot::Sound] is used. Returns [None] if `v` does
+    /// not exist.
+    pub fn axioms_used(&self, v: &Variable) -> Option<Vec<Variable>> {
+        if !self.contains(v) {
+            return None;
+        }
+        let mut seen = HashSet::from([v]);
+        let mut todo = vec![v];
+        while let Some(u) = todo.pop() {
+            for d in self.env.deps.get(u).into_iter().flatten() {
+                if seen.insert(d) {
+                    todo.push(d);
+                }
+            }
+        }
+        let mut found: Vec<Variable> = seen
+            .into_iter()
+            .filter(|u| self.is_axiom(u) || self.is_unproven_theory(u) || **u == quot_sound())
+            .cloned()
+            .collect();
+        found.sort_by_cached_key(|u| u.to_string());
+        Some(found)
+    }
+    /// Returns every declaration that depends on `v`, directly or through
+    /// other declarations, sorted by name.
+    pub fn dependents(&self, v: &Variable) -> Vec<Variable> {
+        let mut found: Vec<Variable> = self
+            .env
+            .order
+            .iter()
+            .filter(|w| self.env.entries.contains_key(w) && self.depends_on(w, v))
+            .cloned()
+            .collect();
+        found.sort_by_cached_key(|w| w.to_string());
+        found
+    }
+    /// Proves the theorem represented by `v` using the proof constructed by
+    /// `tactic` and returns that proof. The tactic cannot see `v` itself and
+    /// the constructed proof goes through [Context::extend_type] like any
+    /// other proof.
+    pub fn prove_by(
+        &mut self,
+        v: &Variable,
+        tactic: &Tactic,
+        trk: &mut SubstTracker,
+    ) -> Result<Expr, Error> {
+        let goal = match self.lookup_global(v) {
+            Some(goal) => goal.clone(),
+            None => return Err(Error::VariableNotFound),
+        };
+        let expr = tactic.run(&self.hiding(v), &goal, trk)?;
+        self.extend_type(v, expr.clone(), trk)?;
+        Ok(expr)
+    }
+    /// Returns the name and type of every axiom or theorem whose type matches
+    /// `pattern`, sorted by name. [WILDCARD](crate::WILDCARD) matches any
+    /// expression, and the hypotheses of a type may be listed in any order.
+    pub fn search_by_type(&self, pattern: &Expr, trk: &mut SubstTracker) -> Vec<(Variable, Expr)> {
+        let mut found: Vec<(Variable, Expr)> = self
+            .hypotheses()
+            .filter(|(_, typ)| search::matches(typ, pattern, trk))
+            .map(|(v, typ)| (v.clone(), typ.clone()))
+            .collect();
+        found.sort_by_cached_key(|(v, _)| v.to_string());
+        found
+    }
+    /// Suggests proofs for the theorem represented by `v` that consist of
+    /// applying a single axiom or theorem. The theorem itself is never used.
+    pub fn suggest_proofs(&self, v: &Variable, trk: &mut SubstTracker) -> Result<Vec<Expr>, Error> {
+        let goal = match self.lookup_global(v) {
+            Some(goal) => goal,
+            None => return Err(Error::VariableNotFound),
+        };
+        Ok(tactic::suggestions(&self.hiding(v), goal, trk))
+    }
     /// Inserts element into the context without checking anything.
     /// Assumed checks:
     ///     1- v is not an axiom
     ///     2- expr type checks
     /// Returns the old proof of the existing theorem if it exists, or returns none otherwise.
     fn extend_type_unchecked(&mut self, v: &Variable, mut expr: Expr) -> Option<Expr> {
-        let entry = self.ctx.get_mut(v).unwrap();
+        let entry = self.env_mut().entries.get_mut(v).unwrap();
         match entry {
-            Entry::Axiom(_) => unreachable!(),
+            Entry::Axiom(_) | Entry::Record(_) | Entry::Def(_) => unreachable!(),
             Entry::Proved(p) => {
                 mem::swap(&mut p.val, &mut expr);
                 Some(expr)
@@ -129,6 +825,7 @@ impl Context {
                 let p = ProvedTheoremEntry {
                     val: expr,
                     typ: u.typ.clone(),
+                    reducibility: Reducibility::Opaque,
                 };
                 *entry = Entry::Proved(p);
                 None
@@ -138,7 +835,7 @@ impl Context {
     /// Adds a new theorem without its proof. Returns error
     /// if a theorem or an axiom with the same name exists
     /// or if the theorem does not type check under the
-    /// current [Context]
+    /// current [Context] or is not a type
     pub fn add_theorem(
         &mut self,
         v: Variable,
@@ -148,16 +845,102 @@ impl Context {
         if self.contains(&v) {
             return Err(Error::AlreadyExists);
         }
-        if typ.type_infer(self.clone(), trk).is_none() {
-            return Err(Error::ExprDoesNotTypeCheck(None));
+        self.check_type(&typ, trk)?;
+        let deps = self.constants([&typ]);
+        self.env_mut().deps.insert(v.clone(), deps);
+        self.declare(
+            v,
+            Entry::Unproved(UnprovedTheoremEntry { typ }),
+            Kind::Theorem,
+        );
+        Ok(())
+    }
+    /// Returns the universe `typ` lives in, or an error if `typ` does not
+    /// type check or is not a type.
+    fn check_type(&self, typ: &Expr, trk: &mut SubstTracker) -> Result<Universe, Error> {
+        let k = typ
+            .type_infer(self, trk)
+            .ok_or(Error::ExprDoesNotTypeCheck(None))?;
+        match k.normalize(self, trk) {
+            Some(Expr::Uni(u)) => Ok(u),
+            _ => Err(Error::NotAType(Box::new(typ.clone()))),
+        }
+    }
+    /// Adds a new definition `v: typ` with the value `val`. Unlike proven
+    /// theorems, definitions unfold during normalization unless
+    /// `reducibility` says otherwise. Returns error if a declaration with the
+    /// same name exists, if `typ` is not a type or if `val` does not have
+    /// the type `typ`.
+    pub fn add_def(
+        &mut self,
+        v: Variable,
+        typ: Expr,
+        val: Expr,
+        reducibility: Reducibility,
+        trk: &mut SubstTracker,
+    ) -> Result<(), Error> {
+        if self.contains(&v) {
+            return Err(Error::AlreadyExists);
+        }
+        self.check_type(&typ, trk)?;
+        let typ2 = val
+            .type_infer(self, trk)
+            .ok_or(Error::ExprDoesNotTypeCheck(None))?;
+        if !self.types_equal(trk, &typ, &typ2) {
+            return Err(Error::TypesDoesNotMatch(Box::new((typ, typ2))));
         }
-        self.ctx
-            .insert(v, Entry::Unproved(UnprovedTheoremEntry { typ }));
+        self.insert_def(v, typ, val, reducibility);
+        Ok(())
+    }
+    /// Adds the definition `v: typ` whose value `val` is already checked.
+    fn insert_def(&mut self, v: Variable, typ: Expr, val: Expr, reducibility: Reducibility) {
+        let deps = self.constants([&typ, &val]);
+        self.env_mut().deps.insert(v.clone(), deps);
+        let entry = Entry::Def(DefEntry {
+            typ,
+            val,
+            reducibility,
+        });
+        self.declare(v, entry, Kind::Definition);
+    }
+    /// Adds a new definition whose type is inferred from `val` and returns
+    /// that type. Returns error if a declaration with the same name exists or
+    /// if `val` does not type check.
+    pub fn add_def_inferred(
+        &mut self,
+        v: Variable,
+        val: Expr,
+        reducibility: Reducibility,
+        trk: &mut SubstTracker,
+    ) -> Result<Expr, Error> {
+        if self.contains(&v) {
+            return Err(Error::AlreadyExists);
+        }
+        let typ = val
+            .type_infer(self, trk)
+            .ok_or(Error::ExprDoesNotTypeCheck(None))?;
+        self.insert_def(v, typ.clone(), val, reducibility);
+        Ok(typ)
+    }
+    /// Adds the theorem `v: typ` together with its proof `val`. Nothing is
+    /// added unless both [Context::add_theorem] and [Context::extend_type]
+    /// succeed.
+    pub fn add_proved_theorem(
+        &mut self,
+        v: Variable,
+        typ: Expr,
+        val: Expr,
+        trk: &mut SubstTracker,
+    ) -> Result<(), Error> {
+        let mut ctx = self.clone();
+        ctx.add_theorem(v.clone(), typ, trk)?;
+        ctx.extend_type(&v, val, trk)?;
+        *self = ctx;
         Ok(())
     }
     /// Adds a new axiom. Returns error if a theorem or an axiom with
     /// the same name exists or if the axiom does not type check under
-    /// the current [Context]
+    /// the current [Context] or is not a type
     pub fn add_axiom(
         &mut self,
         v: Variable,
@@ -167,21 +950,339 @@ impl Context {
         if self.contains(&v) {
             return Err(Error::AlreadyExists);
         }
-        if typ.type_infer(self.clone(), trk).is_none() {
-            return Err(Error::ExprDoesNotTypeCheck(None));
+        self.check_type(&typ, trk)?;
+        let deps = self.constants([&typ]);
+        self.env_mut().deps.insert(v.clone(), deps);
+        self.declare(v, Entry::Axiom(AxiomEntry { typ }), Kind::Axiom);
+        Ok(())
+    }
+    /// Adds a new record type together with a projection function `R.f` for
+    /// every field `f`. Returns error if any of those names already exists,
+    /// if two fields share a name, if a parameter or field type does not
+    /// type check, if a field type does not fit in the record's sort
+    /// (fields of a record in `Prop` must all be propositions), or if another
+    /// record type has the same field names since record values are typed by
+    /// their field names.
+    pub fn add_record(&mut self, rec: Record, trk: &mut SubstTracker) -> Result<(), Error> {
+        self.add_record_entry(rec, false, trk)
+    }
+    /// Adds a new type class, that is a record type whose values are found by
+    /// instance resolution. It is checked the same way as
+    /// [Context::add_record] but the record argument of every projection
+    /// function is instance implicit, so `(Add.add A)` picks the instance of
+    /// `(Add A)` by itself.
+    pub fn add_class(&mut self, rec: Record, trk: &mut SubstTracker) -> Result<(), Error> {
+        self.add_record_entry(rec, true, trk)
+    }
+    /// Adds a new instance `v: typ` with the value `val`. `typ` must be a
+    /// class applied to its parameters, possibly under Π binders, where
+    /// instance implicit binders are the instances this one depends on.
+    /// Returns error if `typ` is not the type of an instance, or for the
+    /// same reasons as [Context::add_def]. Instances are reducible definitions
+    /// so that the fields of an instance compute.
+    pub fn add_instance(
+        &mut self,
+        v: Variable,
+        typ: Expr,
+        val: Expr,
+        trk: &mut SubstTracker,
+    ) -> Result<(), Error> {
+        let norm = typ
+            .normalize(self, trk)
+            .ok_or(Error::ExprDoesNotTypeCheck(None))?;
+        if !self.is_instance_type(&norm) {
+            return Err(Error::NotAClass(Box::new(typ)));
         }
-        self.ctx.insert(v, Entry::Axiom(AxiomEntry { typ }));
+        let mut ctx = self.clone();
+        ctx.add_def(v.clone(), typ, val, Reducibility::Reducible, trk)?;
+        ctx.env_mut().meta.get_mut(&v).unwrap().kind = Kind::Instance;
+        ctx.env_mut().instances.push_back(v);
+        *self = ctx;
         Ok(())
     }
-    /// Returns a new context with unproven theory with name
-    /// [v] and body [ty].
-    pub(crate) fn with_type(&self, v: Variable, typ: Expr) -> Self {
+    /// Registers the axiom or theorem `v` as a coercion, the elaborator then
+    /// inserts it wherever a value of its source type is used where its
+    /// target type, a function or a type is expected.
+    /// The elaborated declarations mention every coercion inserted in them,
+    /// so they are among the [Context::dependents] of `v`.
+    ///
+    /// The source is the first explicit argument of `v` that no later
+    /// argument type and the result type depend on, the arguments before it
+    /// must either be instance implicit or appear in the source type so that
+    /// they can be found by unification. For instance
+    /// `Π A: U, Π x: (List A), (Vec A (length A x))` coerces from `(List A)`.
+    /// Returns error if `v` does not exist, is a record type, is already a
+    /// coercion or has no such argument.
+    pub fn add_coercion(&mut self, v: &Variable, trk: &mut SubstTracker) -> Result<(), Error> {
+        if self.is_record(v) {
+            return Err(Error::NotACoercion(Box::new(Expr::Var(v.clone()))));
+        }
+        if self.coercion_arity(v).is_some() {
+            return Err(Error::AlreadyExists);
+        }
+        let typ = self
+            .lookup_global(v)
+            .ok_or(Error::VariableNotFound)?
+            .clone();
+        let mut e = typ
+            .normalize(self, trk)
+            .ok_or(Error::ExprDoesNotTypeCheck(None))?;
+        let mut params: Vec<Variable> = vec![];
+        let mut arity = 0;
+        while let Expr::Pi(p) = e {
+            arity += 1;
+            if !p.inst && !mentions(&p.e, &p.x) {
+                if params.iter().all(|x| mentions(&p.t, x)) {
+                    self.env_mut().coercions.push_back((v.clone(), arity));
+                    return Ok(());
+                }
+                break;
+            }
+            if !p.inst {
+                params.push(p.x.clone());
+            }
+            e = *p.e;
+        }
+        Err(Error::NotACoercion(Box::new(typ)))
+    }
+    fn add_record_entry(
+        &mut self,
+        rec: Record,
+        class: bool,
+        trk: &mut SubstTracker,
+    ) -> Result<(), Error> {
+        let Record {
+            name,
+            params,
+            sort,
+            fields,
+        } = rec;
+        let projections: Vec<Variable> = fields.iter().map(|(f, _)| name.qualify(f)).collect();
+        if self.contains(&name) || projections.iter().any(|p| self.contains(p)) {
+            return Err(Error::AlreadyExists);
+        }
+        for (i, (f, _)) in fields.iter().enumerate() {
+            if fields[..i].iter().any(|(g, _)| f == g) {
+                return Err(Error::AlreadyExists);
+            }
+        }
+        let same = |rec: &RecordEntry| {
+            rec.fields.len() == fields.len()
+                && rec
+                    .fields
+                    .iter()
+                    .all(|(f, _)| fields.iter().any(|(g, _)| f == g))
+        };
+        if let Some((other, _)) = self.records().find(|(_, rec)| same(rec)) {
+            return Err(Error::SameFields(other.clone()));
+        }
+        let mut local = self.clone();
+        for (x, t) in &params {
+            local.check_type(t, trk)?;
+            local = local.with_local(x.clone(), t.clone());
+        }
+        for (f, t) in &fields {
+            let k = local.check_type(t, trk)?;
+            let fits = match sort {
+                Universe::Prop => k == Universe::Prop,
+                Universe::Type(_) => k <= sort,
+            };
+            if !fits {
+                return Err(Error::ExprDoesNotTypeCheck(None));
+            }
+            local = local.with_local(f.clone(), t.clone());
+        }
+        let typ = params.iter().rev().fold(Expr::Uni(sort), |e, (x, t)| {
+            Expr::Pi(Pi {
+                x: x.clone(),
+                t: Box::new(t.clone()),
+                e: Box::new(e),
+                inst: false,
+                _ty: PhantomData,
+            })
+        });
+        let this_var = trk.refresh_var(&Variable::Str(StrVar {
+            name: "self".to_owned(),
+        }));
+        let this = Expr::Var(this_var.clone());
+        let this_typ = params.iter().fold(Expr::Var(name.clone()), |e, (x, _)| {
+            Expr::App(Application {
+                e1: Box::new(e),
+                e2: Box::new(Expr::Var(x.clone())),
+            })
+        });
+        let entry = RecordEntry {
+            typ,
+            params,
+            fields,
+            class,
+        };
         let mut ctx = self.clone();
-        ctx.ctx
-            .insert(v, Entry::Unproved(UnprovedTheoremEntry { typ }));
-        ctx
+        let mut deps = HashSet::new();
+        let mut bound = vec![];
+        for (x, t) in entry.params.iter().chain(&entry.fields) {
+            constants(self, t, &mut bound, &mut deps);
+            bound.push(x.clone());
+        }
+        ctx.env_mut().deps.insert(name.clone(), deps);
+        let kind = if class { Kind::Class } else { Kind::Record };
+        ctx.declare(name.clone(), Entry::Record(entry.clone()), kind);
+        let args: Vec<Expr> = entry
+            .params
+            .iter()
+            .map(|(x, _)| Expr::Var(x.clone()))
+            .collect();
+        let mut earlier = vec![];
+        for (i, (f, _)) in entry.fields.iter().enumerate() {
+            let field = Expr::Proj(Proj {
+                e: Box::new(this.clone()),
+                field: f.clone(),
+            });
+            let typ = Expr::Pi(Pi {
+                x: this_var.clone(),
+                t: Box::new(this_typ.clone()),
+                e: Box::new(entry.field_type(i, &args, &earlier, trk)),
+                inst: class,
+                _ty: PhantomData,
+            });
+            let val = Expr::Lambda(Lambda {
+                x: this_var.clone(),
+                t: Box::new(this_typ.clone()),
+                e: Box::new(field.clone()),
+                inst: class,
+                _ty: PhantomData,
+            });
+            let (typ, val) = entry
+                .params
+                .iter()
+                .rev()
+                .fold((typ, val), |(typ, val), (x, t)| {
+                    let typ = Expr::Pi(Pi {
+                        x: x.clone(),
+                        t: Box::new(t.clone()),
+                        e: Box::new(typ),
+                        inst: false,
+                        _ty: PhantomData,
+                    });
+                    let val = Expr::Lambda(Lambda {
+                        x: x.clone(),
+                        t: Box::new(t.clone()),
+                        e: Box::new(val),
+                        inst: false,
+                        _ty: PhantomData,
+                    });
+                    (typ, val)
+                });
+            ctx.add_def(
+                projections[i].clone(),
+                typ,
+                val,
+                Reducibility::Reducible,
+                trk,
+            )?;
+            ctx.env_mut().meta.get_mut(&projections[i]).unwrap().kind = Kind::Projection;
+            earlier.push(field);
+        }
+        *self = ctx;
+        Ok(())
+    }
+    /// Elaborates `e` (see [Elaborated]), coercing it to `expected` if given.
+    /// Proof obligations are named after `name` and added to the context as
+    /// unproven theorems.
+    pub fn elaborate(
+        &mut self,
+        name: &Variable,
+        e: &Expr,
+        expected: Option<&Expr>,
+        trk: &mut SubstTracker,
+    ) -> Result<Elaborated, Error> {
+        let mut elab = Elaborator::new(self.clone(), name, trk);
+        let expr = match expected {
+            Some(t) => elab.check(e, t)?,
+            None => elab.infer(e)?.0,
+        };
+        *self = elab.ctx;
+        Ok(Elaborated {
+            expr,
+            obligations: elab.obligations,
+        })
+    }
+    /// Elaborates the type `e` like [Context::elaborate] does, coercing it
+    /// to a sort if it is not a type already.
+    pub fn elaborate_type(
+        &mut self,
+        name: &Variable,
+        e: &Expr,
+        trk: &mut SubstTracker,
+    ) -> Result<Elaborated, Error> {
+        let mut elab = Elaborator::new(self.clone(), name, trk);
+        let expr = elab.infer_type(e)?;
+        *self = elab.ctx;
+        Ok(Elaborated {
+            expr,
+            obligations: elab.obligations,
+        })
+    }
+    /// Returns a new context where the variable `v` of type `typ` is bound
+    /// on top of the local telescope.
+    pub(crate) fn with_local(&self, v: Variable, typ: Expr) -> Self {
+        let local = Local {
+            x: v,
+            t: typ,
+            outer: self.locals.clone(),
+        };
+        Context {
+            env: self.env.clone(),
+            locals: Locals(Some(Rc::new(local))),
+            unfold_irreducible: self.unfold_irreducible,
+            hidden: self.hidden.clone(),
+        }
+    }
+    /// Returns a new context where the declaration `v` does not exist, so
+    /// that proof search for `v` cannot use `v` itself.
+    pub(crate) fn hiding(&self, v: &Variable) -> Self {
+        Context {
+            hidden: Some(v.clone()),
+            ..self.clone()
+        }
+    }
+    /// Removes the innermost bound variable.
+    pub(crate) fn pop_local(&mut self) {
+        if let Some(l) = self.locals.0.take() {
+            self.locals = l.outer.clone();
+        }
+    }
+    /// Runs `f` on this [Context] without its bound variables, what `f`
+    /// declares is kept.
+    pub(crate) fn globally<T>(&mut self, f: impl FnOnce(&mut Context) -> T) -> T {
+        let locals = std::mem::take(&mut self.locals);
+        let result = f(self);
+        self.locals = locals;
+        result
+    }
+    /// Iterates over the name and type of every bound variable, innermost
+    /// first, shadowed ones included.
+    pub(crate) fn bound_variables(&self) -> impl Iterator<Item = (&Variable, &Expr)> {
+        self.locals.iter().map(|l| (&l.x, &l.t))
     }
+    /// Returns [true] if `e1` and `e2` are definitionally equal, that is they
+    /// have the same normal form up to alpha equivalence, or they are both
+    /// proofs of the same proposition (definitional proof irrelevance).
+    ///
+    /// [Reducibility::Irreducible] declarations are only unfolded if the two
+    /// sides are not equal otherwise.
     pub fn types_equal(&self, trk: &mut SubstTracker, e1: &Expr, e2: &Expr) -> bool {
+        if self.types_equal_once(trk, e1, e2) {
+            return true;
+        }
+        if self.unfold_irreducible {
+            return false;
+        }
+        let mut ctx = self.clone();
+        ctx.unfold_irreducible = true;
+        ctx.types_equal_once(trk, e1, e2)
+    }
+    fn types_equal_once(&self, trk: &mut SubstTracker, e1: &Expr, e2: &Expr) -> bool {
         let e1 = match e1.normalize(self, trk) {
             Some(e) => e,
             None => return false,
@@ -190,34 +1291,624 @@ impl Context {
             Some(e) => e,
             None => return false,
         };
-        normalized_types_equal(&e1, &e2, trk)
+        normalized_types_equal(self, &e1, &e2, trk)
+    }
+    /// Returns [true] if `e1` and `e2` have the same type and that type is a
+    /// proposition, so they are equal by proof irrelevance, or a record type
+    /// without fields, so both are equal to `{ }` by eta.
+    fn irrelevant_equal(&self, e1: &Expr, e2: &Expr, trk: &mut SubstTracker) -> bool {
+        let t1 = match e1.type_infer(self, trk) {
+            Some(t) => t,
+            None => return false,
+        };
+        let is_prop = t1
+            .type_infer(self, trk)
+            .and_then(|k| k.normalize(self, trk));
+        if !matches!(is_prop, Some(Expr::Uni(Universe::Prop))) && !self.is_unit(&t1, trk) {
+            return false;
+        }
+        match e2.type_infer(self, trk) {
+            Some(t2) => self.types_equal(trk, &t1, &t2),
+            None => false,
+        }
+    }
+    /// Returns [true] if `typ` is a record type without fields applied to its
+    /// parameters.
+    fn is_unit(&self, typ: &Expr, trk: &mut SubstTracker) -> bool {
+        let mut head = match typ.normalize(self, trk) {
+            Some(t) => t,
+            None => return false,
+        };
+        while let Expr::App(a) = head {
+            head = *a.e1;
+        }
+        match head {
+            Expr::Var(r) => matches!(self.lookup_record(&r), Some(rec) if rec.fields.is_empty()),
+            _ => false,
+        }
+    }
+    /// Returns [true] if `e1` and `e2` type check with the same type.
+    fn same_type(&self, e1: &Expr, e2: &Expr, trk: &mut SubstTracker) -> bool {
+        match (e1.type_infer(self, trk), e2.type_infer(self, trk)) {
+            (Some(t1), Some(t2)) => self.types_equal(trk, &t1, &t2),
+            _ => false,
+        }
     }
 }
 
-fn normalized_types_equal(e1: &Expr, e2: &Expr, trk: &mut SubstTracker) -> bool {
-    match (e1, e2) {
+/// Conversion check for normalized expressions, `ctx` is needed to tell
+/// which terms are proofs.
+fn normalized_types_equal(ctx: &Context, e1: &Expr, e2: &Expr, trk: &mut SubstTracker) -> bool {
+    let structural = match (e1, e2) {
         (Expr::Var(v1), Expr::Var(v2)) => v1 == v2,
         (
             Expr::App(Application { e1: e11, e2: e12 }),
             Expr::App(Application { e1: e21, e2: e22 }),
-        ) => normalized_types_equal(e11, e21, trk) && normalized_types_equal(e12, e22, trk),
+        ) => {
+            normalized_types_equal(ctx, e11, e21, trk) && normalized_types_equal(ctx, e12, e22, trk)
+        }
         (Expr::Uni(u1), Expr::Uni(u2)) => u1 == u2,
-        (Expr::Lambda(l1), Expr::Lambda(l2)) => normalized_abs_equal(l1, l2, trk),
-        (Expr::Pi(p1), Expr::Pi(p2)) => normalized_abs_equal(p1, p2, trk),
+        (Expr::Lambda(l1), Expr::Lambda(l2)) => normalized_abs_equal(ctx, l1, l2, trk),
+        (Expr::Pi(p1), Expr::Pi(p2)) => normalized_abs_equal(ctx, p1, p2, trk),
+        (Expr::Sigma(s1), Expr::Sigma(s2)) => normalized_abs_equal(ctx, s1, s2, trk),
+        (Expr::Pair(p1), Expr::Pair(p2)) => {
+            normalized_types_equal(ctx, &p1.fst, &p2.fst, trk)
+                && normalized_types_equal(ctx, &p1.snd, &p2.snd, trk)
+        }
+        (Expr::Quot(q1), Expr::Quot(q2)) if discriminant(q1) == discriminant(q2) => q1
+            .children()
+            .into_iter()
+            .zip(q2.children())
+            .all(|(c1, c2)| normalized_types_equal(ctx, c1, c2, trk)),
+        (Expr::Rec(r1), Expr::Rec(r2)) => {
+            r1.fields.len() == r2.fields.len()
+                && r1
+                    .fields
+                    .iter()
+                    .all(|(f, c1)| match r2.fields.iter().find(|(g, _)| f == g) {
+                        Some((_, c2)) => normalized_types_equal(ctx, c1, c2, trk),
+                        None => false,
+                    })
+        }
+        (Expr::Proj(p1), Expr::Proj(p2)) => {
+            p1.field == p2.field && normalized_types_equal(ctx, &p1.e, &p2.e, trk)
+        }
+        // eta for pairs: `e = pair T e.fst e.snd`
+        (Expr::Pair(p), e) | (e, Expr::Pair(p)) => {
+            [(&p.fst, FST), (&p.snd, SND)].into_iter().all(|(c, f)| {
+                let proj = Expr::Proj(Proj {
+                    e: Box::new(e.clone()),
+                    field: Variable::Str(StrVar { name: f.to_owned() }),
+                });
+                normalized_types_equal(ctx, c, &proj, trk)
+            })
+        }
+        // eta for records: `e = { f := e.f, ... }` when `e` is a value of
+        // the record type.
+        (Expr::Rec(r), e) | (e, Expr::Rec(r)) => {
+            ctx.same_type(&Expr::Rec(r.clone()), e, trk)
+                && r.fields.iter().all(|(f, c)| {
+                    let proj = Expr::Proj(Proj {
+                        e: Box::new(e.clone()),
+                        field: f.clone(),
+                    });
+                    normalized_types_equal(ctx, c, &proj, trk)
+                })
+        }
         (_, _) => false,
+    };
+    // Two different canonical forms are compared piecewise above, so only
+    // a stuck term can be a proof, or a value of a record type without
+    // fields, that differs from the other side.
+    structural || ((is_neutral(e1) || is_neutral(e2)) && ctx.irrelevant_equal(e1, e2, trk))
+}
+
+/// Returns [true] if the normalized `e` is stuck on a variable, or is the
+/// primitive proof `quot_sound` which has no other canonical form to compare.
+fn is_neutral(e: &Expr) -> bool {
+    match e {
+        Expr::Var(_) | Expr::Proj(_) => true,
+        Expr::Quot(Quot::Lift { .. } | Quot::Sound { .. }) => true,
+        Expr::App(a) => is_neutral(&a.e1),
+        _ => false,
     }
 }
 
 fn normalized_abs_equal<T>(
+    ctx: &Context,
     a1: &Abstraction<T>,
     a2: &Abstraction<T>,
     trk: &mut SubstTracker,
 ) -> bool {
-    if !normalized_types_equal(&a1.t, &a2.t, trk) {
+    if !normalized_types_equal(ctx, &a1.t, &a2.t, trk) {
         return false;
     }
     let mut e2 = a2.e.clone();
     let xvar = Expr::Var(a1.x.clone());
     e2.subst(&a2.x, &xvar, trk);
-    normalized_types_equal(&a1.e, &e2, trk)
+    let ctx2 = ctx.with_local(a1.x.clone(), a1.t.as_ref().clone());
+    normalized_types_equal(&ctx2, &a1.e, &e2, trk)
+}
+
+/// Collects the entries of `ctx` that occur free in `e` and are not in `bound`.
+fn constants(ctx: &Context, e: &Expr, bound: &mut Vec<Variable>, found: &mut HashSet<Variable>) {
+    match e {
+        Expr::Var(v) => {
+            if ctx.contains(v) && !bound.contains(v) {
+                found.insert(v.clone());
+            }
+        }
+        Expr::Uni(_) => (),
+        Expr::App(a) => {
+            constants(ctx, &a.e1, bound, found);
+            constants(ctx, &a.e2, bound, found);
+        }
+        Expr::Pi(p) => constants_abs(ctx, p, bound, found),
+        Expr::Lambda(l) => constants_abs(ctx, l, bound, found),
+        Expr::Sigma(s) => constants_abs(ctx, s, bound, found),
+        Expr::Pair(p) => {
+            constants(ctx, &p.typ, bound, found);
+            constants(ctx, &p.fst, bound, found);
+            constants(ctx, &p.snd, bound, found);
+        }
+        Expr::Quot(q) => {
+            if let Quot::Sound { .. } = q {
+                found.insert(quot_sound());
+            }
+            for c in q.children() {
+                constants(ctx, c, bound, found);
+            }
+        }
+        Expr::Rec(r) => {
+            for (_, c) in &r.fields {
+                constants(ctx, c, bound, found);
+            }
+        }
+        Expr::Proj(p) => {
+            if let Some(f) = ctx.projection_function(p) {
+                if !matches!(p.e.as_ref(), Expr::Var(r) if bound.contains(r)) {
+                    found.insert(f);
+                }
+            }
+            constants(ctx, &p.e, bound, found);
+        }
+    }
+}
+
+/// The pseudo declaration every use of [Quot::Sound] depends on.
+fn quot_sound() -> Variable {
+    Variable::Str(StrVar {
+        name: QUOT_SOUND.to_owned(),
+    })
+}
+
+fn constants_abs<T>(
+    ctx: &Context,
+    a: &Abstraction<T>,
+    bound: &mut Vec<Variable>,
+    found: &mut HashSet<Variable>,
+) {
+    constants(ctx, &a.t, bound, found);
+    bound.push(a.x.clone());
+    constants(ctx, &a.e, bound, found);
+    bound.pop();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+    use crate::{ast::*, Context, Error, SubstTracker, TypeInfer};
+
+    #[test]
+    fn proofs_of_the_same_proposition_are_equal() {
+        let (ctx, mut trk) = axioms([("P", prop()), ("p", v("P")), ("q", v("P"))]);
+        assert!(ctx.types_equal(&mut trk, &v("p"), &v("q")));
+    }
+
+    #[test]
+    fn proofs_under_binders_are_equal() {
+        let (ctx, mut trk) = axioms([
+            ("A", typ(0)),
+            ("P", pi("x", v("A"), prop())),
+            ("f", pi("x", v("A"), app(v("P"), [v("x")]))),
+            ("g", pi("x", v("A"), app(v("P"), [v("x")]))),
+            ("a", v("A")),
+        ]);
+        let fa = app(v("f"), [v("a")]);
+        let ga = app(v("g"), [v("a")]);
+        assert!(ctx.types_equal(&mut trk, &fa, &ga));
+    }
+
+    #[test]
+    fn values_of_a_type_are_not_irrelevant() {
+        let (ctx, mut trk) = axioms([("A", typ(0)), ("a", v("A")), ("b", v("A"))]);
+        assert!(!ctx.types_equal(&mut trk, &v("a"), &v("b")));
+    }
+
+    #[test]
+    fn proofs_of_different_propositions_differ() {
+        let (ctx, mut trk) = axioms([("P", prop()), ("Q", prop()), ("p", v("P")), ("q", v("Q"))]);
+        assert!(!ctx.types_equal(&mut trk, &v("p"), &v("q")));
+    }
+
+    fn rec<const N: usize>(fields: [(&str, Expr); N]) -> Expr {
+        let fields = fields.into_iter().map(|(f, e)| (var(f), e)).collect();
+        Expr::Rec(Rec { fields })
+    }
+
+    /// `record Point : type 0 { x : A, y : A }` with `p : Point`.
+    fn point() -> (Context, SubstTracker) {
+        let (mut ctx, mut trk) = axioms([("A", typ(0)), ("a", v("A")), ("b", v("A"))]);
+        let point = Record {
+            name: var("Point"),
+            params: vec![],
+            sort: Universe::Type(0),
+            fields: vec![(var("x"), v("A")), (var("y"), v("A"))],
+        };
+        ctx.add_record(point, &mut trk).unwrap();
+        ctx.add_axiom(var("p"), v("Point"), &mut trk).unwrap();
+        (ctx, trk)
+    }
+
+    #[test]
+    fn projections_compute_on_record_values() {
+        let (ctx, mut trk) = point();
+        let r = rec([("x", v("a")), ("y", v("b"))]);
+        assert!(ctx.types_equal(&mut trk, &proj(r.clone(), "x"), &v("a")));
+        assert!(ctx.types_equal(&mut trk, &proj(r, "y"), &v("b")));
+    }
+
+    #[test]
+    fn records_are_equal_to_their_eta_expansion() {
+        let (ctx, mut trk) = point();
+        let eta = rec([("y", proj(v("p"), "y")), ("x", proj(v("p"), "x"))]);
+        assert!(ctx.types_equal(&mut trk, &v("p"), &eta));
+        assert!(ctx.types_equal(&mut trk, &eta, &v("p")));
+    }
+
+    #[test]
+    fn eta_keeps_fields_apart() {
+        let (ctx, mut trk) = point();
+        let swapped = rec([("x", proj(v("p"), "y")), ("y", proj(v("p"), "x"))]);
+        assert!(!ctx.types_equal(&mut trk, &v("p"), &swapped));
+    }
+
+    #[test]
+    fn projection_functions_compute() {
+        let (ctx, mut trk) = point();
+        let x = v("Point.x");
+        let r = rec([("x", v("a")), ("y", v("b"))]);
+        assert!(ctx.types_equal(&mut trk, &app(x.clone(), [r]), &v("a")));
+        let px = app(x, [v("p")]);
+        let t = px.type_infer(&ctx, &mut trk).unwrap();
+        assert!(ctx.types_equal(&mut trk, &t, &v("A")));
+        assert!(ctx.types_equal(&mut trk, &px, &proj(v("p"), "x")));
+    }
+
+    #[test]
+    fn records_with_the_same_fields_are_rejected() {
+        let (mut ctx, mut trk) = point();
+        let vec2 = Record {
+            name: var("Vec2"),
+            params: vec![],
+            sort: Universe::Type(0),
+            fields: vec![(var("y"), v("A")), (var("x"), v("A"))],
+        };
+        match ctx.add_record(vec2, &mut trk) {
+            Err(Error::SameFields(other)) => assert_eq!(other, var("Point")),
+            _ => panic!("Vec2 should clash with Point"),
+        }
+    }
+
+    /// `record Two (T : type 0) : type 0 { l : T, r : T }` with `q : Two A`.
+    fn two() -> (Context, SubstTracker) {
+        let (mut ctx, mut trk) = point();
+        let two = Record {
+            name: var("Two"),
+            params: vec![(var("T"), typ(0))],
+            sort: Universe::Type(0),
+            fields: vec![(var("l"), v("T")), (var("r"), v("T"))],
+        };
+        ctx.add_record(two, &mut trk).unwrap();
+        ctx.add_axiom(var("q"), app(v("Two"), [v("A")]), &mut trk)
+            .unwrap();
+        (ctx, trk)
+    }
+
+    #[test]
+    fn eta_holds_for_records_with_parameters() {
+        let (ctx, mut trk) = two();
+        let eta = rec([("l", proj(v("q"), "l")), ("r", proj(v("q"), "r"))]);
+        assert!(ctx.types_equal(&mut trk, &v("q"), &eta));
+        let swapped = rec([("l", proj(v("q"), "r")), ("r", proj(v("q"), "l"))]);
+        assert!(!ctx.types_equal(&mut trk, &v("q"), &swapped));
+        let l = app(v("Two.l"), [v("A"), rec([("l", v("a")), ("r", v("b"))])]);
+        assert!(ctx.types_equal(&mut trk, &l, &v("a")));
+    }
+
+    /// `record Unit : type 0 { }` with `u1 u2 : Unit`.
+    fn unit() -> (Context, SubstTracker) {
+        let (mut ctx, mut trk) = point();
+        let unit = Record {
+            name: var("Unit"),
+            params: vec![],
+            sort: Universe::Type(0),
+            fields: vec![],
+        };
+        ctx.add_record(unit, &mut trk).unwrap();
+        ctx.add_axiom(var("u1"), v("Unit"), &mut trk).unwrap();
+        ctx.add_axiom(var("u2"), v("Unit"), &mut trk).unwrap();
+        (ctx, trk)
+    }
+
+    #[test]
+    fn values_of_a_record_without_fields_are_equal() {
+        let (ctx, mut trk) = unit();
+        assert!(ctx.types_equal(&mut trk, &v("u1"), &v("u2")));
+        assert!(ctx.types_equal(&mut trk, &rec([]), &v("u1")));
+    }
+
+    #[test]
+    fn eta_needs_a_value_of_the_record_type() {
+        let (ctx, mut trk) = unit();
+        assert!(!ctx.types_equal(&mut trk, &rec([]), &v("a")));
+        assert!(!ctx.types_equal(&mut trk, &v("p"), &rec([])));
+    }
+
+    #[test]
+    fn only_theorems_can_be_proven() {
+        let (mut ctx, mut trk) = point();
+        let def = ctx.add_def(var("c"), v("A"), v("a"), Reducibility::Reducible, &mut trk);
+        assert!(def.is_ok());
+        let proof = ctx.extend_type(&var("c"), v("b"), &mut trk);
+        assert!(matches!(proof, Err(Error::NotATheorem(_))));
+        let proof = ctx.extend_type(&var("Point"), v("p"), &mut trk);
+        assert!(matches!(proof, Err(Error::NotATheorem(_))));
+        let proof = ctx.extend_type(&var("a"), v("b"), &mut trk);
+        assert!(matches!(proof, Err(Error::CannotProveAxiom)));
+    }
+
+    /// A reducible theorem `t : A := a` and `u : P t := pa` which only type
+    /// checks because `t` unfolds to `a`.
+    fn dependent() -> (Context, SubstTracker) {
+        let (mut ctx, mut trk) = axioms([
+            ("A", typ(0)),
+            ("a", v("A")),
+            ("b", v("A")),
+            ("P", pi("x", v("A"), prop())),
+            ("pa", app(v("P"), [v("a")])),
+        ]);
+        ctx.add_proved_theorem(var("t"), v("A"), v("a"), &mut trk)
+            .unwrap();
+        ctx.set_reducibility(&var("t"), Reducibility::Reducible)
+            .unwrap();
+        let pt = app(v("P"), [v("t")]);
+        ctx.add_proved_theorem(var("u"), pt, v("pa"), &mut trk)
+            .unwrap();
+        (ctx, trk)
+    }
+
+    #[test]
+    fn proving_twice_is_rejected() {
+        let (mut ctx, mut trk) = dependent();
+        let proof = ctx.extend_type(&var("t"), v("a"), &mut trk);
+        assert!(matches!(proof, Err(Error::AlreadyProven)));
+    }
+
+    #[test]
+    fn reprove_keeps_dependents_that_still_check() {
+        let (mut ctx, mut trk) = dependent();
+        let old = ctx.reprove(&var("t"), v("a"), &mut trk).unwrap();
+        assert!(matches!(old, Some(Expr::Var(x)) if x == var("a")));
+    }
+
+    #[test]
+    fn reprove_rejects_breaking_dependents_and_changes_nothing() {
+        let (mut ctx, mut trk) = dependent();
+        match ctx.reprove(&var("t"), v("b"), &mut trk) {
+            Err(Error::BrokenDependents(broken)) => assert_eq!(broken, vec![var("u")]),
+            _ => panic!("u should be broken"),
+        }
+        let val = ctx.lookup_value(&var("t"));
+        assert!(matches!(val, Some(Expr::Var(x)) if *x == var("a")));
+    }
+
+    #[test]
+    fn axioms_used_follows_dependencies() {
+        let (ctx, _) = dependent();
+        let used = ctx.axioms_used(&var("u")).unwrap();
+        assert_eq!(used, vec![var("A"), var("P"), var("a"), var("pa")]);
+    }
+
+    #[test]
+    fn axioms_used_counts_unproven_theorems() {
+        let (mut ctx, mut trk) = axioms([("A", typ(0))]);
+        ctx.add_theorem(var("s"), v("A"), &mut trk).unwrap();
+        ctx.add_def(var("c"), v("A"), v("s"), Reducibility::Reducible, &mut trk)
+            .unwrap();
+        let used = ctx.axioms_used(&var("c")).unwrap();
+        assert_eq!(used, vec![var("A"), var("s")]);
+    }
+
+    #[test]
+    fn axioms_used_counts_quotient_soundness() {
+        let (mut ctx, mut trk) = axioms([
+            ("A", typ(0)),
+            ("R", pi("x", v("A"), pi("y", v("A"), prop()))),
+            ("a", v("A")),
+            ("b", v("A")),
+            ("r", app(v("R"), [v("a"), v("b")])),
+        ]);
+        let q = quot(v("A"), v("R"));
+        let eq_ab = eq(q, mk(v("R"), v("a")), mk(v("R"), v("b")));
+        let s = sound(v("R"), v("a"), v("b"), v("r"));
+        ctx.add_proved_theorem(var("t"), eq_ab, s, &mut trk)
+            .unwrap();
+        let used = ctx.axioms_used(&var("t")).unwrap();
+        assert!(used.contains(&var(QUOT_SOUND)));
+        assert!(!ctx
+            .dependencies(&var("t"))
+            .unwrap()
+            .contains(&var(QUOT_SOUND)));
+    }
+
+    fn is_circular_through(proof: Result<(), Error>, v: &str) -> bool {
+        matches!(proof, Err(Error::CircularProof(d)) if d == var(v))
+    }
+
+    #[test]
+    fn theorems_cannot_prove_themselves() {
+        let (mut ctx, mut trk) = axioms([("P", prop())]);
+        ctx.add_theorem(var("t"), v("P"), &mut trk).unwrap();
+        let proof = ctx.extend_type(&var("t"), v("t"), &mut trk);
+        assert!(is_circular_through(proof, "t"));
+        assert!(ctx.is_unproven_theory(&var("t")));
+    }
+
+    #[test]
+    fn theorems_cannot_prove_each_other() {
+        let (mut ctx, mut trk) = axioms([("P", prop())]);
+        ctx.add_theorem(var("a"), v("P"), &mut trk).unwrap();
+        ctx.add_theorem(var("b"), v("P"), &mut trk).unwrap();
+        ctx.extend_type(&var("b"), v("a"), &mut trk).unwrap();
+        let proof = ctx.extend_type(&var("a"), v("b"), &mut trk);
+        assert!(is_circular_through(proof, "b"));
+        assert!(ctx.is_unproven_theory(&var("a")));
+    }
+
+    #[test]
+    fn circular_proofs_through_definitions_are_rejected() {
+        let (mut ctx, mut trk) = axioms([("P", prop())]);
+        ctx.add_theorem(var("t"), v("P"), &mut trk).unwrap();
+        ctx.add_def(var("d"), v("P"), v("t"), Reducibility::Reducible, &mut trk)
+            .unwrap();
+        let proof = ctx.extend_type(&var("t"), v("d"), &mut trk);
+        assert!(is_circular_through(proof, "d"));
+    }
+
+    #[test]
+    fn dependencies_are_the_entries_mentioned_directly() {
+        let (mut ctx, mut trk) = axioms([("A", typ(0)), ("a", v("A"))]);
+        let id = lam("x", v("A"), v("x"));
+        ctx.add_def(
+            var("id"),
+            pi("x", v("A"), v("A")),
+            id,
+            Reducibility::Reducible,
+            &mut trk,
+        )
+        .unwrap();
+        ctx.add_proved_theorem(var("t"), v("A"), app(v("id"), [v("a")]), &mut trk)
+            .unwrap();
+        let deps = ctx.dependencies(&var("t")).unwrap();
+        assert_eq!(deps, vec![var("A"), var("a"), var("id")]);
+        assert_eq!(ctx.dependencies(&var("id")).unwrap(), vec![var("A")]);
+        assert!(ctx.dependencies(&var("u")).is_none());
+    }
+
+    #[test]
+    fn inferred_definitions_record_their_type() {
+        let (mut ctx, mut trk) = axioms([("A", typ(0)), ("a", v("A"))]);
+        let val = app(lam("x", v("A"), v("x")), [v("a")]);
+        let typ = ctx
+            .add_def_inferred(var("d"), val, Reducibility::Reducible, &mut trk)
+            .unwrap();
+        assert!(ctx.types_equal(&mut trk, &typ, &v("A")));
+        assert!(ctx.is_definition(&var("d")));
+        let recorded = ctx.lookup_global(&var("d")).unwrap().clone();
+        assert!(ctx.types_equal(&mut trk, &recorded, &v("A")));
+        let bad = app(v("a"), [v("a")]);
+        assert!(ctx
+            .add_def_inferred(var("e"), bad, Reducibility::Reducible, &mut trk)
+            .is_err());
+        assert!(!ctx.contains(&var("e")));
+    }
+
+    #[test]
+    fn failed_proved_theorems_are_not_added() {
+        let (mut ctx, mut trk) = axioms([("A", typ(0)), ("B", typ(0)), ("a", v("A"))]);
+        let wrong = ctx.add_proved_theorem(var("t"), v("B"), v("a"), &mut trk);
+        assert!(matches!(wrong, Err(Error::TypesDoesNotMatch(_))));
+        assert!(!ctx.contains(&var("t")));
+        let bad = ctx.add_proved_theorem(var("t"), v("A"), app(v("a"), [v("a")]), &mut trk);
+        assert!(bad.is_err());
+        assert!(!ctx.contains(&var("t")));
+    }
+
+    #[test]
+    fn rollback_undoes_declarations_and_proofs() {
+        let (mut ctx, mut trk) = axioms([("A", typ(0))]);
+        ctx.add_theorem(var("t"), v("A"), &mut trk).unwrap();
+        let cp = ctx.checkpoint();
+        ctx.add_axiom(var("a"), v("A"), &mut trk).unwrap();
+        ctx.extend_type(&var("t"), v("a"), &mut trk).unwrap();
+        ctx.rollback(cp);
+        assert!(!ctx.contains(&var("a")));
+        assert!(ctx.is_unproven_theory(&var("t")));
+    }
+
+    #[test]
+    fn failed_transactions_change_nothing() {
+        let (mut ctx, mut trk) = axioms([("A", typ(0))]);
+        let cp = ctx.checkpoint();
+        let result = ctx.transaction(|ctx| {
+            ctx.add_axiom(var("a"), v("A"), &mut trk)?;
+            ctx.add_axiom(var("a"), v("A"), &mut trk)
+        });
+        assert!(matches!(result, Err(Error::AlreadyExists)));
+        assert!(!ctx.contains(&var("a")));
+        assert!(!ctx.changed_since(&cp));
+    }
+
+    #[test]
+    fn successful_transactions_are_kept() {
+        let (mut ctx, mut trk) = axioms([("A", typ(0))]);
+        let cp = ctx.checkpoint();
+        let result = ctx.transaction(|ctx| ctx.add_axiom(var("a"), v("A"), &mut trk));
+        assert!(result.is_ok());
+        assert!(ctx.contains(&var("a")));
+        assert!(ctx.changed_since(&cp));
+    }
+
+    #[test]
+    fn declarations_since_a_checkpoint_are_listed() {
+        let (mut ctx, mut trk) = point();
+        let cp = ctx.checkpoint();
+        assert_eq!(ctx.declared_since(&cp).count(), 0);
+        let wrap = Record {
+            name: var("Wrap"),
+            params: vec![],
+            sort: Universe::Type(0),
+            fields: vec![(var("get"), v("A"))],
+        };
+        ctx.add_record(wrap, &mut trk).unwrap();
+        ctx.add_theorem(var("t"), v("A"), &mut trk).unwrap();
+        let added: Vec<Variable> = ctx.declared_since(&cp).map(|d| d.name.clone()).collect();
+        assert_eq!(added, vec![var("Wrap"), var("Wrap.get"), var("t")]);
+    }
+
+    #[test]
+    fn hidden_declarations_do_not_exist() {
+        let (mut ctx, mut trk) = axioms([("A", typ(0)), ("a", v("A"))]);
+        ctx.add_theorem(var("t"), v("A"), &mut trk).unwrap();
+        let hidden = ctx.hiding(&var("t"));
+        assert!(!hidden.contains(&var("t")));
+        assert!(hidden.declarations().all(|d| *d.name != var("t")));
+        let found = hidden.search_by_type(&v("A"), &mut trk);
+        assert_eq!(found.len(), 1);
+        assert!(ctx.contains(&var("t")));
+    }
+
+    #[test]
+    fn records_in_prop_only_have_proofs() {
+        let (mut ctx, mut trk) = axioms([("A", typ(0)), ("P", prop())]);
+        let rec = |name: &str, f: &str, t: Expr| Record {
+            name: var(name),
+            params: vec![],
+            sort: Universe::Prop,
+            fields: vec![(var(f), t)],
+        };
+        assert!(ctx.add_record(rec("Proofs", "x", v("P")), &mut trk).is_ok());
+        assert!(ctx.add_record(rec("Data", "y", v("A")), &mut trk).is_err());
+    }
 }
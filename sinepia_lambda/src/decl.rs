@@ -0,0 +1,46 @@
+use crate::ast::*;
+
+/// What a declaration of the [Context](crate::Context) is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    Axiom,
+    /// Proven or not.
+    Theorem,
+    Definition,
+    Record,
+    Class,
+    /// Projection function `R.f` generated for the field `f` of the record
+    /// type or class `R`.
+    Projection,
+    Instance,
+}
+
+/// Where a declaration was written.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Location {
+    /// [None] when the declaration was typed interactively.
+    pub file: Option<String>,
+    pub line: usize,
+}
+
+/// What is known about a declaration besides its type and value.
+#[derive(Clone, Debug)]
+pub struct Metadata {
+    pub kind: Kind,
+    pub location: Option<Location>,
+    pub doc: Option<String>,
+}
+
+/// A view of a declaration of the [Context](crate::Context).
+pub struct Declaration<'a> {
+    pub name: &'a Variable,
+    pub typ: &'a Expr,
+    /// The proof or body, [None] for axioms, unproven theorems and record
+    /// types.
+    pub value: Option<&'a Expr>,
+    pub meta: &'a Metadata,
+    /// Only definitions and proven theorems have a reducibility.
+    pub reducibility: Option<Reducibility>,
+    /// [true] if the declaration is registered as a coercion.
+    pub coercion: bool,
+}
@@ -0,0 +1,769 @@
+use crate::typecheck::infer_universe;
+use crate::unify::{mentions, Unifier};
+use crate::{ast::*, Context, Error, Normalize, Subst, SubstTracker, TypeInfer, Uinf, WILDCARD};
+use std::{collections::HashMap, marker::PhantomData};
+
+/// How many instances deep instance resolution may go, an instance whose
+/// own instance implicit arguments need instances counts as one level.
+pub const INSTANCE_DEPTH: Uinf = 8;
+
+/// Output of [Context::elaborate].
+pub struct Elaborated {
+    /// The elaborated expression.
+    pub expr: Expr,
+    /// Name and type of every proof obligation generated while elaborating.
+    /// They are added to the context as unproven theorems.
+    pub obligations: Vec<(Variable, Expr)>,
+}
+
+/// A metavariable standing for a Π binder, its type and whether the binder
+/// is instance implicit.
+type Binder = (Variable, Expr, bool);
+
+/// What a value is coerced to.
+enum Target<'a> {
+    Type(&'a Expr),
+    Function,
+    Sort,
+}
+
+/// Elaboration turns what users write into what the kernel accepts by
+/// inserting coercions:
+/// - a value of a subset type `{ x: A | P }` used where `A` is expected
+///   becomes its first projection.
+/// - a value `a: A` used where `{ x: A | P }` is expected becomes
+///   `pair { x: A | P } a h` where `h` is a new proof obligation `P[a/x]`.
+/// - otherwise the coercions registered by [Context::add_coercion] are
+///   tried, the most recent first, to turn a value into one of the
+///   expected type, a value applied to an argument into a function, and a
+///   binder type or declared type into a type.
+///
+/// It also fills in the arguments of instance implicit binders `[x: C A]`
+/// by instance resolution: the bound variables of enclosing instance
+/// implicit binders are tried first, then the instances in the [Context]
+/// starting from the most recent one. Instances may themselves take
+/// instance implicit arguments which are resolved recursively up to
+/// [INSTANCE_DEPTH].
+///
+/// The output is type checked again by the kernel, so elaboration is not
+/// trusted.
+pub(crate) struct Elaborator<'a> {
+    /// Global context extended with the obligations so far, together with
+    /// the variables bound by the binders we are under.
+    pub(crate) ctx: Context,
+    /// Obligations are named after the declaration being elaborated.
+    prefix: &'a Variable,
+    pub(crate) obligations: Vec<(Variable, Expr)>,
+    /// Instances found so far for goals that do not mention bound variables.
+    cache: HashMap<String, Expr>,
+    trk: &'a mut SubstTracker,
+}
+
+impl<'a> Elaborator<'a> {
+    pub(crate) fn new(ctx: Context, prefix: &'a Variable, trk: &'a mut SubstTracker) -> Self {
+        Elaborator {
+            ctx,
+            prefix,
+            obligations: vec![],
+            cache: Default::default(),
+            trk,
+        }
+    }
+    fn under<R>(&mut self, x: &Variable, t: &Expr, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.ctx = self.ctx.with_local(x.clone(), t.clone());
+        let r = f(self);
+        self.ctx.pop_local();
+        r
+    }
+    /// Returns the elaborated expression together with its type.
+    pub(crate) fn infer(&mut self, e: &Expr) -> Result<(Expr, Expr), Error> {
+        match e {
+            Expr::Var(_) | Expr::Uni(_) => self.kernel(e.clone()),
+            Expr::App(a) => {
+                let (f, tf) = self.infer(&a.e1)?;
+                let (f, tf) = if self.is_explicit_instance(&tf, &a.e2) {
+                    (f, tf)
+                } else {
+                    self.instantiate(f, tf)?
+                };
+                let (f, tf) = match tf.normalize(&self.ctx, self.trk) {
+                    Some(Expr::Pi(_)) => (f, tf),
+                    _ => match self.coercion(&f, &tf, Target::Function) {
+                        Some(f) => self.kernel(f)?,
+                        None => return Err(Error::ExprDoesNotTypeCheck(None)),
+                    },
+                };
+                let p = match tf.normalize(&self.ctx, self.trk) {
+                    Some(Expr::Pi(p)) => p,
+                    _ => return Err(Error::ExprDoesNotTypeCheck(None)),
+                };
+                let arg = self.check(&a.e2, &p.t)?;
+                let mut ret = *p.e;
+                ret.subst(&p.x, &arg, self.trk);
+                let app = Expr::App(Application {
+                    e1: Box::new(f),
+                    e2: Box::new(arg),
+                });
+                Ok((app, ret))
+            }
+            Expr::Pi(p) => {
+                let abs = self.abstraction(p)?;
+                self.kernel(Expr::Pi(abs))
+            }
+            Expr::Lambda(l) => {
+                let abs = self.abstraction(l)?;
+                self.kernel(Expr::Lambda(abs))
+            }
+            Expr::Sigma(s) => {
+                let abs = self.abstraction(s)?;
+                self.kernel(Expr::Sigma(abs))
+            }
+            Expr::Pair(p) => {
+                let (typ, _) = self.infer(&p.typ)?;
+                let s = match typ.normalize(&self.ctx, self.trk) {
+                    Some(Expr::Sigma(s)) => s,
+                    _ => return Err(Error::ExprDoesNotTypeCheck(None)),
+                };
+                let fst = self.check(&p.fst, &s.t)?;
+                let mut tb = *s.e;
+                tb.subst(&s.x, &fst, self.trk);
+                let snd = self.check(&p.snd, &tb)?;
+                Ok((
+                    Expr::Pair(Pair {
+                        typ: Box::new(typ.clone()),
+                        fst: Box::new(fst),
+                        snd: Box::new(snd),
+                    }),
+                    typ,
+                ))
+            }
+            Expr::Quot(q) => {
+                let mut q = q.clone();
+                for c in q.children_mut() {
+                    *c = self.infer(c)?.0;
+                }
+                self.kernel(Expr::Quot(q))
+            }
+            Expr::Rec(r) => {
+                let mut fields = Vec::with_capacity(r.fields.len());
+                for (f, c) in &r.fields {
+                    fields.push((f.clone(), self.infer(c)?.0));
+                }
+                self.kernel(Expr::Rec(Rec { fields }))
+            }
+            Expr::Proj(p) => {
+                let e = match self.ctx.projection_function(p) {
+                    Some(_) => p.e.as_ref().clone(),
+                    None => self.infer(&p.e)?.0,
+                };
+                self.kernel(Expr::Proj(Proj {
+                    e: Box::new(e),
+                    field: p.field.clone(),
+                }))
+            }
+        }
+    }
+    /// Returns the elaborated expression after coercing it to `expected`.
+    pub(crate) fn check(&mut self, e: &Expr, expected: &Expr) -> Result<Expr, Error> {
+        let (e, t) = self.infer(e)?;
+        let wants_inst = matches!(
+            expected.normalize(&self.ctx, self.trk),
+            Some(Expr::Pi(p)) if p.inst
+        );
+        let (e, t) = if wants_inst {
+            (e, t)
+        } else {
+            self.instantiate(e, t)?
+        };
+        self.coerce(e, &t, expected)
+    }
+    /// Returns [true] if `t` starts with an instance implicit binder and `arg`
+    /// is already an instance of its class, so `arg` is given explicitly
+    /// instead of being resolved.
+    fn is_explicit_instance(&mut self, t: &Expr, arg: &Expr) -> bool {
+        let ctx = &self.ctx;
+        let p = match t.normalize(ctx, self.trk) {
+            Some(Expr::Pi(p)) if p.inst => p,
+            _ => return false,
+        };
+        match arg.type_infer(ctx, self.trk) {
+            Some(a) => ctx.types_equal(self.trk, &a, &p.t),
+            None => false,
+        }
+    }
+    /// Applies `e: t` to instances for as long as `t` starts with instance
+    /// implicit binders.
+    fn instantiate(&mut self, mut e: Expr, mut t: Expr) -> Result<(Expr, Expr), Error> {
+        loop {
+            let p = match t.normalize(&self.ctx, self.trk) {
+                Some(Expr::Pi(p)) if p.inst => p,
+                _ => return Ok((e, t)),
+            };
+            let i = self.resolve(&p.t)?;
+            t = *p.e;
+            t.subst(&p.x, &i, self.trk);
+            e = Expr::App(Application {
+                e1: Box::new(e),
+                e2: Box::new(i),
+            });
+        }
+    }
+    /// Finds an instance of the class `goal`.
+    pub(crate) fn resolve(&mut self, goal: &Expr) -> Result<Expr, Error> {
+        let ctx = self.ctx.clone();
+        self.search(&ctx, goal, INSTANCE_DEPTH)
+            .ok_or_else(|| Error::InstanceNotFound(Box::new(goal.clone())))
+    }
+    fn search(&mut self, ctx: &Context, goal: &Expr, depth: Uinf) -> Option<Expr> {
+        if depth == 0 {
+            return None;
+        }
+        let goal = goal.normalize(ctx, self.trk)?;
+        let local = |e: &Expr| ctx.bound_variables().any(|(x, _)| mentions(e, x));
+        let key = goal.to_string();
+        if !local(&goal) {
+            if let Some(i) = self.cache.get(&key) {
+                return Some(i.clone());
+            }
+        }
+        let mut candidates: Vec<(Variable, Expr)> = vec![];
+        for (x, t) in ctx.bound_variables() {
+            if candidates.iter().any(|(y, _)| x == y) {
+                continue;
+            }
+            candidates.push((x.clone(), t.clone()));
+        }
+        candidates.retain(|(_, t)| match t.normalize(ctx, self.trk) {
+            Some(t) => ctx.is_instance_type(&t),
+            None => false,
+        });
+        candidates.extend(ctx.instances().map(|(v, t)| (v.clone(), t.clone())));
+        for (v, typ) in candidates {
+            if let Some(i) = self.try_instance(ctx, &v, &typ, &goal, depth) {
+                if !local(&goal) && !local(&i) {
+                    self.cache.insert(key, i.clone());
+                }
+                return Some(i);
+            }
+        }
+        None
+    }
+    /// Tries to solve `goal` with the instance `v: typ`. Arguments of
+    /// explicit binders must be determined by unification, arguments of
+    /// instance implicit binders are resolved recursively.
+    fn try_instance(
+        &mut self,
+        ctx: &Context,
+        v: &Variable,
+        typ: &Expr,
+        goal: &Expr,
+        depth: Uinf,
+    ) -> Option<Expr> {
+        let (binders, concl) = self.telescope(ctx, typ, usize::MAX)?;
+        let mut unifier = Unifier::new(binders.iter().map(|(m, _, _)| m.clone()).collect());
+        if !unifier.unify(&concl, goal, self.trk) {
+            return None;
+        }
+        self.apply(ctx, v, binders, unifier, depth)
+    }
+    /// Splits at most `limit` leading Π binders of `typ` into [Binder]s.
+    fn telescope(
+        &mut self,
+        ctx: &Context,
+        typ: &Expr,
+        limit: usize,
+    ) -> Option<(Vec<Binder>, Expr)> {
+        let mut concl = typ.normalize(ctx, self.trk)?;
+        let mut binders = vec![];
+        while binders.len() < limit {
+            let p = match concl {
+                Expr::Pi(p) => p,
+                _ => break,
+            };
+            let m = self.trk.refresh_var(&p.x);
+            let mut e = *p.e;
+            e.subst(&p.x, &Expr::Var(m.clone()), self.trk);
+            binders.push((m, *p.t, p.inst));
+            concl = e;
+        }
+        Some((binders, concl))
+    }
+    /// Applies `v` to the solutions of the metavariables in `binders`, those
+    /// of instance implicit binders left unsolved are resolved.
+    fn apply(
+        &mut self,
+        ctx: &Context,
+        v: &Variable,
+        binders: Vec<Binder>,
+        mut unifier: Unifier,
+        depth: Uinf,
+    ) -> Option<Expr> {
+        let mut inst = Expr::Var(v.clone());
+        for (m, t, is_inst) in binders {
+            let arg = match unifier.lookup(&m) {
+                Some(arg) => unifier.instantiate(arg, self.trk),
+                None if is_inst => {
+                    let t = unifier.instantiate(&t, self.trk);
+                    if unifier.has_unsolved(&t) {
+                        return None;
+                    }
+                    let arg = self.search(ctx, &t, depth - 1)?;
+                    unifier.assign(m, arg.clone());
+                    arg
+                }
+                None => return None,
+            };
+            inst = Expr::App(Application {
+                e1: Box::new(inst),
+                e2: Box::new(arg),
+            });
+        }
+        Some(inst)
+    }
+    /// Applies the most recent coercion that turns `e: from` into a value of
+    /// the given target.
+    fn coercion(&mut self, e: &Expr, from: &Expr, target: Target) -> Option<Expr> {
+        let ctx = self.ctx.clone();
+        let from = from.normalize(&ctx, self.trk)?;
+        let to = match target {
+            Target::Type(to) => Some(to.normalize(&ctx, self.trk)?),
+            _ => None,
+        };
+        let coercions: Vec<(Variable, usize, Expr)> = self
+            .ctx
+            .coercions()
+            .map(|(c, n, t)| (c.clone(), n, t.clone()))
+            .collect();
+        for (c, arity, typ) in coercions {
+            let (binders, concl) = match self.telescope(&ctx, &typ, arity) {
+                Some(split) if split.0.len() == arity => split,
+                _ => continue,
+            };
+            let (m, src, _) = binders.last().unwrap().clone();
+            let mut unifier = Unifier::new(binders.iter().map(|(m, _, _)| m.clone()).collect());
+            if !unifier.unify(&src, &from, self.trk) {
+                continue;
+            }
+            let fits = match &to {
+                Some(to) => unifier.unify(&concl, to, self.trk),
+                None => {
+                    let concl = unifier.instantiate(&concl, self.trk);
+                    matches!(
+                        (concl.normalize(&ctx, self.trk), &target),
+                        (Some(Expr::Pi(_)), Target::Function) | (Some(Expr::Uni(_)), Target::Sort)
+                    )
+                }
+            };
+            if !fits {
+                continue;
+            }
+            unifier.assign(m, e.clone());
+            if let Some(coerced) = self.apply(&ctx, &c, binders, unifier, INSTANCE_DEPTH) {
+                return Some(coerced);
+            }
+        }
+        None
+    }
+    /// Coerces the type `t: k` to a sort if `k` is not one already.
+    fn coerce_to_sort(&mut self, t: Expr, k: &Expr) -> Result<Expr, Error> {
+        if let Some(Expr::Uni(_)) = k.normalize(&self.ctx, self.trk) {
+            return Ok(t);
+        }
+        self.coercion(&t, k, Target::Sort)
+            .ok_or_else(|| Error::NotAType(Box::new(t.clone())))
+    }
+    /// Returns the elaborated type `e` coerced to a sort if needed.
+    pub(crate) fn infer_type(&mut self, e: &Expr) -> Result<Expr, Error> {
+        let (t, k) = self.infer(e)?;
+        let (t, k) = self.instantiate(t, k)?;
+        self.coerce_to_sort(t, &k)
+    }
+    fn coerce(&mut self, e: Expr, from: &Expr, to: &Expr) -> Result<Expr, Error> {
+        let ctx = self.ctx.clone();
+        if ctx.types_equal(self.trk, from, to) {
+            return Ok(e);
+        }
+        if let Some(Expr::Sigma(s)) = from.normalize(&ctx, self.trk) {
+            if self.is_subset(&s) && ctx.types_equal(self.trk, &s.t, to) {
+                return Ok(Expr::Proj(Proj {
+                    e: Box::new(e),
+                    field: Variable::Str(StrVar {
+                        name: FST.to_owned(),
+                    }),
+                }));
+            }
+        }
+        if let Some(Expr::Sigma(s)) = to.normalize(&ctx, self.trk) {
+            if self.is_subset(&s) && ctx.types_equal(self.trk, from, &s.t) {
+                let mut goal = *s.e;
+                goal.subst(&s.x, &e, self.trk);
+                let proof = self.obligation(goal)?;
+                return Ok(Expr::Pair(Pair {
+                    typ: Box::new(to.clone()),
+                    fst: Box::new(e),
+                    snd: Box::new(proof),
+                }));
+            }
+        }
+        match self.coercion(&e, from, Target::Type(to)) {
+            Some(coerced) => Ok(coerced),
+            None => Err(Error::TypesDoesNotMatch(Box::new((
+                to.clone(),
+                from.clone(),
+            )))),
+        }
+    }
+    /// Returns [true] if `Σ x: A, P` is a subset type, that is `P` is a proposition.
+    fn is_subset(&mut self, s: &Sigma) -> bool {
+        let ctx = self.ctx.with_local(s.x.clone(), s.t.as_ref().clone());
+        infer_universe(&ctx, &s.e, self.trk) == Some(Universe::Prop)
+    }
+    /// Adds a new unproven theorem `Π locals, goal` and returns it applied to
+    /// the bound variables.
+    fn obligation(&mut self, goal: Expr) -> Result<Expr, Error> {
+        let mut n = self.obligations.len();
+        let name = loop {
+            n += 1;
+            let name = Variable::Str(StrVar {
+                name: format!("{}_obligation_{}", self.prefix, n),
+            });
+            if !self.ctx.globally(|ctx| ctx.contains(&name)) {
+                break name;
+            }
+        };
+        let locals: Vec<(Variable, Expr)> = self
+            .ctx
+            .bound_variables()
+            .map(|(x, t)| (x.clone(), t.clone()))
+            .collect();
+        let typ = locals.iter().fold(goal, |e, (x, t)| {
+            Expr::Pi(Pi {
+                x: x.clone(),
+                t: Box::new(t.clone()),
+                e: Box::new(e),
+                inst: false,
+                _ty: PhantomData,
+            })
+        });
+        let trk = &mut *self.trk;
+        self.ctx
+            .globally(|ctx| ctx.add_theorem(name.clone(), typ.clone(), trk))?;
+        self.obligations.push((name.clone(), typ));
+        Ok(locals.iter().rev().fold(Expr::Var(name), |e, (x, _)| {
+            Expr::App(Application {
+                e1: Box::new(e),
+                e2: Box::new(Expr::Var(x.clone())),
+            })
+        }))
+    }
+    fn abstraction<T>(&mut self, a: &Abstraction<T>) -> Result<Abstraction<T>, Error> {
+        let t = self.infer_type(&a.t)?;
+        // anonymous instance binders `[C A]` get a name of their own so
+        // that resolution can refer to each of them.
+        let x = match &a.x {
+            Variable::Str(s) if a.inst && s.name == WILDCARD => self.trk.refresh_var(&a.x),
+            x => x.clone(),
+        };
+        let (e, _) = self.under(&x, &t, |el| el.infer(&a.e))?;
+        Ok(Abstraction {
+            x,
+            t: Box::new(t),
+            e: Box::new(e),
+            inst: a.inst,
+            _ty: a._ty,
+        })
+    }
+    /// Leaves `e` as is and asks the kernel for its type.
+    fn kernel(&mut self, e: Expr) -> Result<(Expr, Expr), Error> {
+        match e.type_infer(&self.ctx, self.trk) {
+            Some(t) => Ok((e, t)),
+            None => Err(Error::ExprDoesNotTypeCheck(None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Elaborator;
+    use crate::test_util::*;
+    use crate::{ast::*, Context, Elaborated, Error, SubstTracker, INSTANCE_DEPTH};
+
+    /// `P: A → Prop` and the subset type `{ x: A | P x }`.
+    fn subset() -> Expr {
+        sigma("x", v("A"), app(v("P"), [v("x")]))
+    }
+
+    fn subsets() -> (Context, SubstTracker) {
+        axioms([
+            ("A", typ(0)),
+            ("P", pi("x", v("A"), prop())),
+            ("a", v("A")),
+            ("s", subset()),
+            ("f", pi("x", v("A"), v("A"))),
+            ("g", pi("x", subset(), v("A"))),
+        ])
+    }
+
+    fn elaborate(ctx: &mut Context, trk: &mut SubstTracker, name: &str, e: Expr) -> Elaborated {
+        ctx.elaborate(&var(name), &e, None, trk).unwrap()
+    }
+
+    #[test]
+    fn subset_values_are_coerced_to_the_carrier() {
+        let (mut ctx, mut trk) = subsets();
+        let out = elaborate(&mut ctx, &mut trk, "t", app(v("f"), [v("s")]));
+        let expected = app(v("f"), [proj(v("s"), FST)]);
+        assert_eq!(out.expr.to_string(), expected.to_string());
+        assert!(out.obligations.is_empty());
+    }
+
+    #[test]
+    fn carrier_values_are_coerced_to_the_subset_with_an_obligation() {
+        let (mut ctx, mut trk) = subsets();
+        let out = elaborate(&mut ctx, &mut trk, "t", app(v("g"), [v("a")]));
+        let expected = app(v("g"), [pair(subset(), v("a"), v("t_obligation_1"))]);
+        assert_eq!(out.expr.to_string(), expected.to_string());
+        let (name, goal) = &out.obligations[0];
+        assert_eq!(*name, var("t_obligation_1"));
+        assert!(ctx.types_equal(&mut trk, goal, &app(v("P"), [v("a")])));
+        assert!(ctx.is_unproven_theory(name));
+    }
+
+    #[test]
+    fn obligations_under_binders_abstract_over_the_bound_variables() {
+        let (mut ctx, mut trk) = subsets();
+        let e = lam("y", v("A"), app(v("g"), [v("y")]));
+        let out = elaborate(&mut ctx, &mut trk, "t", e);
+        let (_, goal) = &out.obligations[0];
+        let expected = pi("y", v("A"), app(v("P"), [v("y")]));
+        assert!(ctx.types_equal(&mut trk, goal, &expected));
+    }
+
+    #[test]
+    fn obligation_names_skip_existing_declarations() {
+        let (mut ctx, mut trk) = subsets();
+        elaborate(&mut ctx, &mut trk, "t", app(v("g"), [v("a")]));
+        let out = elaborate(&mut ctx, &mut trk, "t", app(v("g"), [v("a")]));
+        assert_eq!(out.obligations[0].0, var("t_obligation_2"));
+    }
+
+    #[test]
+    fn binders_shadow_declarations() {
+        let (mut ctx, mut trk) = subsets();
+        ctx.add_theorem(var("t"), v("A"), &mut trk).unwrap();
+        let prefix = var("u");
+        let mut elab = Elaborator::new(ctx, &prefix, &mut trk);
+        elab.under(&var("t"), &subset(), |elab| {
+            assert!(!elab.ctx.is_unproven_theory(&var("t")));
+            assert!(elab.ctx.lookup_global(&var("t")).is_none());
+            assert!(elab.ctx.lookup_local(&var("t")).is_some());
+        });
+        assert!(elab.ctx.is_unproven_theory(&var("t")));
+    }
+
+    #[test]
+    fn obligation_names_ignore_binders() {
+        let (mut ctx, mut trk) = subsets();
+        elaborate(&mut ctx, &mut trk, "t", app(v("g"), [v("a")]));
+        let e = lam("t_obligation_1", v("A"), app(v("g"), [v("a")]));
+        let out = elaborate(&mut ctx, &mut trk, "t", e);
+        assert_eq!(out.obligations[0].0, var("t_obligation_2"));
+        assert!(ctx.is_unproven_theory(&var("t_obligation_1")));
+    }
+
+    fn inhabited(t: Expr) -> Expr {
+        app(v("Inhabited"), [t])
+    }
+
+    fn boxed(t: Expr) -> Expr {
+        app(v("Box"), [t])
+    }
+
+    /// The class `Inhabited`, an instance for `Nat` and one for `Box A`
+    /// whenever `A` has one, and `pick` taking an instance implicit argument.
+    fn classes() -> (Context, SubstTracker) {
+        let (mut ctx, mut trk) = axioms([
+            ("Nat", typ(0)),
+            ("zero", v("Nat")),
+            ("Void", typ(0)),
+            ("Box", pi("A", typ(0), typ(0))),
+            ("box", pi("A", typ(0), pi("a", v("A"), boxed(v("A"))))),
+        ]);
+        let class = Record {
+            name: var("Inhabited"),
+            params: vec![(var("A"), typ(0))],
+            sort: Universe::Type(0),
+            fields: vec![(var("default"), v("A"))],
+        };
+        ctx.add_class(class, &mut trk).unwrap();
+        let default = |val: Expr| {
+            Expr::Rec(Rec {
+                fields: vec![(var("default"), val)],
+            })
+        };
+        let nat = default(v("zero"));
+        ctx.add_instance(var("instNat"), inhabited(v("Nat")), nat, &mut trk)
+            .unwrap();
+        let inst_box = pi(
+            "A",
+            typ(0),
+            inst_pi("i", inhabited(v("A")), inhabited(boxed(v("A")))),
+        );
+        let inner = app(v("box"), [v("A"), proj(v("i"), "default")]);
+        let val = lam("A", typ(0), lam("i", inhabited(v("A")), default(inner)));
+        ctx.add_instance(var("instBox"), inst_box, val, &mut trk)
+            .unwrap();
+        let pick = pi("A", typ(0), inst_pi("i", inhabited(v("A")), v("A")));
+        ctx.add_axiom(var("pick"), pick, &mut trk).unwrap();
+        (ctx, trk)
+    }
+
+    /// Elaborates `pick t` where a value of `t` is expected.
+    fn pick(ctx: &mut Context, trk: &mut SubstTracker, t: Expr) -> Result<Expr, Error> {
+        let e = app(v("pick"), [t.clone()]);
+        Ok(ctx.elaborate(&var("t"), &e, Some(&t), trk)?.expr)
+    }
+
+    fn resolve(ctx: &Context, trk: &mut SubstTracker, goal: Expr) -> Result<Expr, Error> {
+        let prefix = var("t");
+        Elaborator::new(ctx.clone(), &prefix, trk).resolve(&goal)
+    }
+
+    #[test]
+    fn instance_arguments_are_resolved() {
+        let (mut ctx, mut trk) = classes();
+        let out = pick(&mut ctx, &mut trk, v("Nat")).unwrap();
+        let expected = app(v("pick"), [v("Nat"), v("instNat")]);
+        assert_eq!(out.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn instance_binders_are_candidates() {
+        let (mut ctx, mut trk) = classes();
+        let under = |body| lam("A", typ(0), lam("i", inhabited(v("A")), body));
+        let body = app(v("box"), [v("A"), app(v("pick"), [v("A")])]);
+        let out = elaborate(&mut ctx, &mut trk, "t", under(body));
+        let picked = app(v("pick"), [v("A"), v("i")]);
+        let expected = under(app(v("box"), [v("A"), picked]));
+        assert_eq!(out.expr.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn instances_depending_on_instances_are_chained() {
+        let (mut ctx, mut trk) = classes();
+        let out = pick(&mut ctx, &mut trk, boxed(v("Nat"))).unwrap();
+        let inst = app(v("instBox"), [v("Nat"), v("instNat")]);
+        let expected = app(v("pick"), [boxed(v("Nat")), inst]);
+        assert_eq!(out.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn instances_are_resolved_up_to_the_depth_limit() {
+        let (ctx, mut trk) = classes();
+        let nested = |n| (0..n).fold(v("Nat"), |t, _| boxed(t));
+        let limit = INSTANCE_DEPTH as usize;
+        let deepest = resolve(&ctx, &mut trk, inhabited(nested(limit - 1)));
+        assert!(deepest.is_ok());
+        let too_deep = resolve(&ctx, &mut trk, inhabited(nested(limit)));
+        assert!(matches!(too_deep, Err(Error::InstanceNotFound(_))));
+    }
+
+    #[test]
+    fn missing_instances_are_reported() {
+        let (mut ctx, mut trk) = classes();
+        let out = pick(&mut ctx, &mut trk, boxed(v("Void")));
+        assert!(matches!(out, Err(Error::InstanceNotFound(_))));
+    }
+
+    #[test]
+    fn instances_need_a_class_type() {
+        let (mut ctx, mut trk) = classes();
+        let out = ctx.add_instance(var("i"), v("Nat"), v("zero"), &mut trk);
+        assert!(matches!(out, Err(Error::NotAClass(_))));
+        assert!(!ctx.contains(&var("i")));
+    }
+
+    #[test]
+    fn only_closed_goals_are_cached() {
+        let (ctx, mut trk) = classes();
+        let prefix = var("t");
+        let mut elab = Elaborator::new(ctx, &prefix, &mut trk);
+        elab.resolve(&inhabited(boxed(v("Nat")))).unwrap();
+        let cached: Vec<String> = elab.cache.keys().cloned().collect();
+        assert_eq!(cached.len(), 2);
+        assert!(cached.contains(&inhabited(v("Nat")).to_string()));
+        elab.under(&var("A"), &typ(0), |elab| {
+            elab.under(&var("i"), &inhabited(v("A")), |elab| {
+                let i = elab.resolve(&inhabited(v("A"))).unwrap();
+                assert_eq!(i.to_string(), "i");
+            })
+        });
+        assert_eq!(elab.cache.len(), 2);
+    }
+
+    /// Coercions from `A` to `B`, from `Fn` to functions and from `Set` to
+    /// types.
+    fn coercions() -> (Context, SubstTracker) {
+        let (mut ctx, mut trk) = axioms([
+            ("A", typ(0)),
+            ("B", typ(0)),
+            ("a", v("A")),
+            ("toB", pi("x", v("A"), v("B"))),
+            ("useB", pi("x", v("B"), v("B"))),
+            ("Fn", typ(0)),
+            ("fn", v("Fn")),
+            ("call", pi("f", v("Fn"), pi("x", v("A"), v("A")))),
+            ("Set", typ(1)),
+            ("S", v("Set")),
+            ("El", pi("s", v("Set"), typ(0))),
+        ]);
+        for c in ["toB", "call", "El"] {
+            ctx.add_coercion(&var(c), &mut trk).unwrap();
+        }
+        (ctx, trk)
+    }
+
+    #[test]
+    fn coercions_are_inserted_where_a_type_is_expected() {
+        let (mut ctx, mut trk) = coercions();
+        let out = elaborate(&mut ctx, &mut trk, "t", app(v("useB"), [v("a")]));
+        let expected = app(v("useB"), [app(v("toB"), [v("a")])]);
+        assert_eq!(out.expr.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn coercions_are_inserted_where_a_function_is_expected() {
+        let (mut ctx, mut trk) = coercions();
+        let out = elaborate(&mut ctx, &mut trk, "t", app(v("fn"), [v("a")]));
+        let expected = app(v("call"), [v("fn"), v("a")]);
+        assert_eq!(out.expr.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn coercions_are_inserted_where_a_sort_is_expected() {
+        let (mut ctx, mut trk) = coercions();
+        let out = elaborate(&mut ctx, &mut trk, "t", pi("x", v("S"), v("A")));
+        let expected = pi("x", app(v("El"), [v("S")]), v("A"));
+        assert_eq!(out.expr.to_string(), expected.to_string());
+        let out = ctx.elaborate_type(&var("t"), &v("S"), &mut trk).unwrap();
+        assert_eq!(out.expr.to_string(), app(v("El"), [v("S")]).to_string());
+    }
+
+    #[test]
+    fn inserted_coercions_are_hidden_unless_asked() {
+        let (mut ctx, mut trk) = coercions();
+        let out = elaborate(&mut ctx, &mut trk, "t", app(v("useB"), [v("a")]));
+        assert_eq!(ctx.pretty(&out.expr, false).to_string(), "(useB a)");
+        assert_eq!(ctx.pretty(&out.expr, true).to_string(), "(useB (toB a))");
+    }
+
+    #[test]
+    fn declarations_using_a_coercion_depend_on_it() {
+        let (mut ctx, mut trk) = coercions();
+        let out = ctx.elaborate(&var("b"), &v("a"), Some(&v("B")), &mut trk);
+        let val = out.unwrap().expr;
+        ctx.add_def(var("b"), v("B"), val, Reducibility::Reducible, &mut trk)
+            .unwrap();
+        assert_eq!(ctx.dependents(&var("toB")), vec![var("b")]);
+    }
+}
@@ -1,20 +1,34 @@
 pub mod ast;
 mod context;
+mod decl;
+mod elab;
 mod normalize;
+mod pretty;
+mod search;
 mod subst;
+mod tactic;
+#[cfg(test)]
+mod test_util;
 mod typecheck;
-pub use context::Context;
+mod unify;
+pub use context::{Checkpoint, Context};
+pub use decl::{Declaration, Kind, Location, Metadata};
+pub use elab::{Elaborated, INSTANCE_DEPTH};
 pub use normalize::Normalize;
+pub use pretty::Pretty;
+pub use search::WILDCARD;
 pub use subst::{Subst, SubstTracker};
+pub use tactic::AUTO_DEPTH;
 pub use typecheck::TypeInfer;
 
-use ast::Expr;
+use ast::{Expr, Variable};
 /// In theory this is arbitrarily long integer.
 /// In practice we don't need integer that is that long.
 /// u128 should be sufficient for now.
 pub type Uinf = u128;
 pub type AdditionalErr = Option<Box<Error>>;
 
+#[derive(Debug)]
 pub enum Error {
     /// This error is returned when user tries to extend an axiom
     /// (usually via [Context::extend_type]) with proof.
@@ -25,6 +39,45 @@ pub enum Error {
     /// Returned when an expresssion provided as proof by [Context::extend_type]
     /// fails to typecheck
     ExprDoesNotTypeCheck(AdditionalErr),
-    TypesDoesNotMatch((Expr, Expr)),
+    TypesDoesNotMatch(Box<(Expr, Expr)>),
     AlreadyExists,
+    /// Returned by [Tactic::Decide](ast::Tactic::Decide) when the decision
+    /// procedure does not evaluate to `true`. It carries the normal form of
+    /// the boolean, or of the hypothesis of the soundness proof if that does
+    /// not compute to `Unit`.
+    CannotDecide(Box<Expr>),
+    /// Returned by [Tactic::Auto](ast::Tactic::Auto) when no proof is found
+    /// within the search depth.
+    ProofNotFound,
+    /// Returned by [Context::add_instance] when the type of the instance
+    /// is not a class applied to its parameters.
+    NotAClass(Box<Expr>),
+    /// Returned while elaborating when no instance of the class is found
+    /// within [INSTANCE_DEPTH].
+    InstanceNotFound(Box<Expr>),
+    /// Returned by [Context::add_coercion] when the type of the declaration
+    /// has no argument to coerce from.
+    NotACoercion(Box<Expr>),
+    /// Returned by [Context::extend_type] when the proof mentions the
+    /// theorem being proven, or an entry that depends on it.
+    CircularProof(Variable),
+    /// Returned when the declared type of an axiom, theorem, binder or
+    /// field is an expression that is not a type.
+    NotAType(Box<Expr>),
+    /// Returned by [Context::set_reducibility] when the declaration has no
+    /// value to unfold.
+    NoValue(Variable),
+    /// Returned by [Context::extend_type] when the theorem already has a
+    /// proof, [Context::reprove] replaces proofs.
+    AlreadyProven,
+    /// Returned by [Context::reprove] with the declarations that no longer
+    /// type check with the new proof.
+    BrokenDependents(Vec<Variable>),
+    /// Returned by [Context::extend_type] when given a proof for a
+    /// definition or a record type.
+    NotATheorem(Variable),
+    /// Returned by [Context::add_record] and [Context::add_class] when the
+    /// record type it carries has exactly the same field names, record values
+    /// could not tell the two apart.
+    SameFields(Variable),
 }
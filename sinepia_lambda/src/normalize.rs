@@ -5,8 +5,8 @@ pub trait Normalize {
 
 impl Normalize for Variable {
     fn normalize(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
-        if ctx.contains(self) {
-            match ctx.lookup_value(self) {
+        if ctx.lookup_type(self).is_some() {
+            match ctx.unfold(self) {
                 Some(e) => e.normalize(ctx, trk),
                 None => Some(Expr::Var(self.clone())),
             }
@@ -40,19 +40,91 @@ impl Normalize for Application {
     }
 }
 
+///```text
+///     quot_lift f h (quot_mk R e)  ⟶  f e
+///```
+impl Normalize for Quot {
+    fn normalize(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        let mut q = self.clone();
+        for c in q.children_mut() {
+            *c = c.normalize(ctx, trk)?;
+        }
+        if let Quot::Lift { f, q: arg, .. } = &q {
+            if let Expr::Quot(Quot::Mk { e, .. }) = arg.as_ref() {
+                let app = Application {
+                    e1: f.clone(),
+                    e2: e.clone(),
+                };
+                return app.normalize(ctx, trk);
+            }
+        }
+        Some(Expr::Quot(q))
+    }
+}
+
+impl Normalize for Rec {
+    fn normalize(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        let mut fields = Vec::with_capacity(self.fields.len());
+        for (f, e) in &self.fields {
+            fields.push((f.clone(), e.normalize(ctx, trk)?));
+        }
+        Some(Expr::Rec(Rec { fields }))
+    }
+}
+
+impl Normalize for Pair {
+    fn normalize(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        Some(Expr::Pair(Pair {
+            typ: Box::new(self.typ.normalize(ctx, trk)?),
+            fst: Box::new(self.fst.normalize(ctx, trk)?),
+            snd: Box::new(self.snd.normalize(ctx, trk)?),
+        }))
+    }
+}
+
+///```text
+///     { f := e, ... }.f  ⟶  e
+///     (pair T a b).fst   ⟶  a
+///     (pair T a b).snd   ⟶  b
+///```
+impl Normalize for Proj {
+    fn normalize(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        if let Some(v) = ctx.projection_function(self) {
+            return v.normalize(ctx, trk);
+        }
+        match self.e.normalize(ctx, trk)? {
+            Expr::Rec(r) => r
+                .fields
+                .into_iter()
+                .find(|(f, _)| f == &self.field)
+                .map(|(_, e)| e),
+            Expr::Pair(p) => match &self.field {
+                Variable::Str(s) if s.name == FST => Some(*p.fst),
+                Variable::Str(s) if s.name == SND => Some(*p.snd),
+                _ => None,
+            },
+            e => Some(Expr::Proj(Proj {
+                e: Box::new(e),
+                field: self.field.clone(),
+            })),
+        }
+    }
+}
+
 impl<T> Normalize for Abstraction<T>
 where
     Abstraction<T>: Into<Expr>,
 {
     fn normalize(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
         let t = self.t.normalize(ctx, trk)?;
-        let ctx2 = ctx.with_type(self.x.clone(), t.clone());
+        let ctx2 = ctx.with_local(self.x.clone(), t.clone());
         let e = self.e.normalize(&ctx2, trk)?;
         Some(
             Abstraction::<T> {
                 x: self.x.clone(),
                 t: Box::new(t),
                 e: Box::new(e),
+                inst: self.inst,
                 _ty: self._ty,
             }
             .into(),
@@ -67,6 +139,11 @@ impl Normalize for Expr {
             Expr::Pi(p) => p.normalize(ctx, trk),
             Expr::Lambda(l) => l.normalize(ctx, trk),
             Expr::App(a) => a.normalize(ctx, trk),
+            Expr::Quot(q) => q.normalize(ctx, trk),
+            Expr::Rec(r) => r.normalize(ctx, trk),
+            Expr::Proj(p) => p.normalize(ctx, trk),
+            Expr::Sigma(s) => s.normalize(ctx, trk),
+            Expr::Pair(p) => p.normalize(ctx, trk),
         }
     }
 }
@@ -0,0 +1,161 @@
+use crate::{ast::*, Context, Kind};
+use std::fmt::{Display, Formatter, Result};
+
+/// Displays an expression, see [Context::pretty].
+pub struct Pretty<'a> {
+    pub(crate) expr: &'a Expr,
+    pub(crate) ctx: &'a Context,
+    pub(crate) coercions: bool,
+}
+
+impl Display for Pretty<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if self.coercions {
+            self.expr.fmt(f)
+        } else {
+            hide_coercions(self.ctx, self.expr).fmt(f)
+        }
+    }
+}
+
+/// Replaces every fully applied coercion `(c a₁ ... aₙ x)` with `x`.
+fn hide_coercions(ctx: &Context, e: &Expr) -> Expr {
+    match e {
+        Expr::Var(_) | Expr::Uni(_) => e.clone(),
+        Expr::App(a) => {
+            let mut head = e;
+            let mut args = 0;
+            while let Expr::App(a) = head {
+                head = &a.e1;
+                args += 1;
+            }
+            match head {
+                Expr::Var(c) if ctx.coercion_arity(c) == Some(args) => hide_coercions(ctx, &a.e2),
+                _ => Expr::App(Application {
+                    e1: Box::new(hide_coercions(ctx, &a.e1)),
+                    e2: Box::new(hide_coercions(ctx, &a.e2)),
+                }),
+            }
+        }
+        Expr::Pi(p) => Expr::Pi(hide_coercions_abs(ctx, p)),
+        Expr::Lambda(l) => Expr::Lambda(hide_coercions_abs(ctx, l)),
+        Expr::Sigma(s) => Expr::Sigma(hide_coercions_abs(ctx, s)),
+        Expr::Pair(p) => Expr::Pair(Pair {
+            typ: Box::new(hide_coercions(ctx, &p.typ)),
+            fst: Box::new(hide_coercions(ctx, &p.fst)),
+            snd: Box::new(hide_coercions(ctx, &p.snd)),
+        }),
+        Expr::Quot(q) => {
+            let mut q = q.clone();
+            for c in q.children_mut() {
+                *c = hide_coercions(ctx, c);
+            }
+            Expr::Quot(q)
+        }
+        Expr::Rec(r) => Expr::Rec(Rec {
+            fields: r
+                .fields
+                .iter()
+                .map(|(f, c)| (f.clone(), hide_coercions(ctx, c)))
+                .collect(),
+        }),
+        Expr::Proj(p) => Expr::Proj(Proj {
+            e: Box::new(hide_coercions(ctx, &p.e)),
+            field: p.field.clone(),
+        }),
+    }
+}
+
+fn hide_coercions_abs<T>(ctx: &Context, a: &Abstraction<T>) -> Abstraction<T> {
+    Abstraction {
+        x: a.x.clone(),
+        t: Box::new(hide_coercions(ctx, &a.t)),
+        e: Box::new(hide_coercions(ctx, &a.e)),
+        inst: a.inst,
+        _ty: a._ty,
+    }
+}
+
+/// Expressions are printed using the same syntax the parser accepts.
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Expr::Var(v) => v.fmt(f),
+            Expr::Uni(u) => u.fmt(f),
+            Expr::Pi(p) if p.inst => write!(f, "forall [{} : {}], {}", p.x, p.t, p.e),
+            Expr::Pi(p) => write!(f, "forall ({} : {}), {}", p.x, p.t, p.e),
+            Expr::Lambda(l) if l.inst => write!(f, "lambda [{} : {}], {}", l.x, l.t, l.e),
+            Expr::Lambda(l) => write!(f, "lambda ({} : {}), {}", l.x, l.t, l.e),
+            Expr::App(a) => write!(f, "({} {})", a.e1, a.e2),
+            Expr::Quot(q) => q.fmt(f),
+            Expr::Rec(r) => r.fmt(f),
+            Expr::Proj(p) => write!(f, "{}.{}", p.e, p.field),
+            Expr::Sigma(s) => write!(f, "sigma ({} : {}), {}", s.x, s.t, s.e),
+            Expr::Pair(p) => write!(f, "pair {} {} {}", p.typ, p.fst, p.snd),
+        }
+    }
+}
+
+impl Display for Quot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Quot::Type { a, r } => write!(f, "({} / {})", a, r),
+            Quot::Mk { r, e } => write!(f, "quot_mk {} {}", r, e),
+            Quot::Lift { f: g, h, q } => write!(f, "quot_lift {} {} {}", g, h, q),
+            Quot::Sound { r, a, b, h } => write!(f, "quot_sound {} {} {} {}", r, a, b, h),
+        }
+    }
+}
+
+impl Display for Rec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{{")?;
+        for (i, (field, e)) in self.fields.iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(f, "{}{} := {}", sep, field, e)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+impl Display for Variable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Variable::Str(s) => write!(f, "{}", s.name),
+            Variable::GenSym(s) => write!(f, "{}_{}", s.name, s.id),
+        }
+    }
+}
+
+impl Display for Universe {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Universe::Prop => write!(f, "Prop"),
+            Universe::Type(level) => write!(f, "type {}", level),
+        }
+    }
+}
+
+impl Display for Reducibility {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Reducibility::Reducible => write!(f, "reducible"),
+            Reducibility::Irreducible => write!(f, "irreducible"),
+            Reducibility::Opaque => write!(f, "opaque"),
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Kind::Axiom => write!(f, "axiom"),
+            Kind::Theorem => write!(f, "theorem"),
+            Kind::Definition => write!(f, "def"),
+            Kind::Record => write!(f, "record"),
+            Kind::Class => write!(f, "class"),
+            Kind::Projection => write!(f, "projection"),
+            Kind::Instance => write!(f, "instance"),
+        }
+    }
+}
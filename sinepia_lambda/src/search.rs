@@ -0,0 +1,199 @@
+use crate::unify::Unifier;
+use crate::{ast::*, Subst, SubstTracker};
+
+/// Name that stands for "anything" inside search patterns.
+pub const WILDCARD: &str = "_";
+
+/// Returns [true] if `typ` matches `pattern`.
+///
+/// Every free occurrence of [WILDCARD] in `pattern` matches any expression,
+/// matching is up to alpha equivalence and the hypotheses (the leading Π
+/// binders) of `typ` may appear in any order in `pattern`.
+pub(crate) fn matches(typ: &Expr, pattern: &Expr, trk: &mut SubstTracker) -> bool {
+    let mut metas = vec![];
+    let pattern = wildcards(pattern, &mut metas, trk);
+    let (ps, pc) = telescope(&pattern, usize::MAX, trk);
+    let (xs, xc) = telescope(typ, ps.len(), trk);
+    if xs.len() != ps.len() {
+        return false;
+    }
+    metas.extend(xs.iter().map(|(x, _)| x.clone()));
+    let mut unifier = Unifier::new(metas);
+    if !unifier.unify(&xc, &pc, trk) {
+        return false;
+    }
+    permute(unifier, &xs, &ps, &mut vec![false; ps.len()], trk)
+}
+
+/// Tries to pair every hypothesis in `xs` with a distinct unused hypothesis in `ps`.
+fn permute(
+    unifier: Unifier,
+    xs: &[(Variable, Expr)],
+    ps: &[(Variable, Expr)],
+    used: &mut Vec<bool>,
+    trk: &mut SubstTracker,
+) -> bool {
+    let ((x, a), rest) = match xs.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+    for (j, (p, b)) in ps.iter().enumerate() {
+        if used[j] {
+            continue;
+        }
+        let mut u = unifier.clone();
+        if u.unify(&Expr::Var(x.clone()), &Expr::Var(p.clone()), trk) && u.unify(a, b, trk) {
+            used[j] = true;
+            if permute(u, rest, ps, used, trk) {
+                return true;
+            }
+            used[j] = false;
+        }
+    }
+    false
+}
+
+/// Splits at most `limit` leading Π binders from `e`. Every binder is
+/// renamed to a fresh variable so that binders from different expressions
+/// never clash.
+fn telescope(e: &Expr, limit: usize, trk: &mut SubstTracker) -> (Vec<(Variable, Expr)>, Expr) {
+    let mut binders = vec![];
+    let mut e = e.clone();
+    while binders.len() < limit {
+        let p = match e {
+            Expr::Pi(p) => p,
+            _ => break,
+        };
+        let x = trk.refresh_var(&p.x);
+        let mut body = *p.e;
+        body.subst(&p.x, &Expr::Var(x.clone()), trk);
+        binders.push((x, *p.t));
+        e = body;
+    }
+    (binders, e)
+}
+
+/// Replaces every free occurrence of [WILDCARD] with a fresh metavariable.
+fn wildcards(e: &Expr, metas: &mut Vec<Variable>, trk: &mut SubstTracker) -> Expr {
+    match e {
+        Expr::Var(Variable::Str(s)) if s.name == WILDCARD => {
+            let m = trk.refresh_var(&Variable::Str(s.clone()));
+            metas.push(m.clone());
+            Expr::Var(m)
+        }
+        Expr::Var(_) | Expr::Uni(_) => e.clone(),
+        Expr::App(a) => Expr::App(Application {
+            e1: Box::new(wildcards(&a.e1, metas, trk)),
+            e2: Box::new(wildcards(&a.e2, metas, trk)),
+        }),
+        Expr::Pi(p) => Expr::Pi(wildcards_abs(p, metas, trk)),
+        Expr::Lambda(l) => Expr::Lambda(wildcards_abs(l, metas, trk)),
+        Expr::Sigma(s) => Expr::Sigma(wildcards_abs(s, metas, trk)),
+        Expr::Pair(p) => Expr::Pair(Pair {
+            typ: Box::new(wildcards(&p.typ, metas, trk)),
+            fst: Box::new(wildcards(&p.fst, metas, trk)),
+            snd: Box::new(wildcards(&p.snd, metas, trk)),
+        }),
+        Expr::Quot(q) => {
+            let mut q = q.clone();
+            for c in q.children_mut() {
+                *c = wildcards(c, metas, trk);
+            }
+            Expr::Quot(q)
+        }
+        Expr::Rec(r) => Expr::Rec(Rec {
+            fields: r
+                .fields
+                .iter()
+                .map(|(f, c)| (f.clone(), wildcards(c, metas, trk)))
+                .collect(),
+        }),
+        Expr::Proj(p) => Expr::Proj(Proj {
+            e: Box::new(wildcards(&p.e, metas, trk)),
+            field: p.field.clone(),
+        }),
+    }
+}
+
+fn wildcards_abs<T>(
+    a: &Abstraction<T>,
+    metas: &mut Vec<Variable>,
+    trk: &mut SubstTracker,
+) -> Abstraction<T> {
+    let t = wildcards(&a.t, metas, trk);
+    let shadowed = matches!(&a.x, Variable::Str(s) if s.name == WILDCARD);
+    let e = if shadowed {
+        a.e.as_ref().clone()
+    } else {
+        wildcards(&a.e, metas, trk)
+    };
+    Abstraction {
+        x: a.x.clone(),
+        t: Box::new(t),
+        e: Box::new(e),
+        inst: a.inst,
+        _ty: a._ty,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    fn arrow(a: Expr, b: Expr) -> Expr {
+        pi("_", a, b)
+    }
+
+    #[test]
+    fn wildcards_match_any_expression() {
+        let mut trk = SubstTracker::new();
+        let typ = app(v("P"), [app(v("f"), [v("a")])]);
+        assert!(matches(&typ, &app(v("P"), [v(WILDCARD)]), &mut trk));
+        assert!(matches(
+            &typ,
+            &app(v(WILDCARD), [app(v("f"), [v(WILDCARD)])]),
+            &mut trk
+        ));
+        assert!(!matches(&typ, &app(v("Q"), [v(WILDCARD)]), &mut trk));
+    }
+
+    #[test]
+    fn a_wildcard_binder_does_not_make_its_body_a_pattern() {
+        let mut trk = SubstTracker::new();
+        let typ = pi("x", v("A"), v("B"));
+        assert!(matches(&typ, &pi(WILDCARD, v("A"), v("B")), &mut trk));
+        assert!(!matches(&typ, &pi(WILDCARD, v("A"), v(WILDCARD)), &mut trk));
+    }
+
+    #[test]
+    fn hypotheses_match_in_any_order() {
+        let mut trk = SubstTracker::new();
+        let typ = arrow(v("A"), arrow(v("B"), v("C")));
+        assert!(matches(
+            &typ,
+            &arrow(v("B"), arrow(v("A"), v("C"))),
+            &mut trk
+        ));
+        assert!(!matches(
+            &typ,
+            &arrow(v("A"), arrow(v("A"), v("C"))),
+            &mut trk
+        ));
+        assert!(!matches(&typ, &arrow(v("A"), v("C")), &mut trk));
+    }
+
+    #[test]
+    fn suggestions_apply_one_entry_and_skip_the_goal() {
+        let (mut ctx, mut trk) = axioms([
+            ("A", prop()),
+            ("B", prop()),
+            ("a", v("A")),
+            ("f", arrow(v("A"), v("B"))),
+        ]);
+        ctx.add_theorem(var("b"), v("B"), &mut trk).unwrap();
+        let found = ctx.suggest_proofs(&var("b"), &mut trk).unwrap();
+        let found: Vec<String> = found.iter().map(|e| e.to_string()).collect();
+        assert_eq!(found, vec![app(v("f"), [v("a")]).to_string()]);
+    }
+}
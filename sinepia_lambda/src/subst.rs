@@ -16,7 +16,7 @@ impl SubstTracker {
     pub fn new() -> Self {
         SubstTracker { current_level: 0 }
     }
-    fn refresh_var(&mut self, var: &Variable) -> Variable {
+    pub(crate) fn refresh_var(&mut self, var: &Variable) -> Variable {
         let name = match var {
             Variable::GenSym(s) => s.name.to_owned(),
             Variable::Str(s) => s.name.to_owned(),
@@ -44,6 +44,11 @@ impl Subst for Expr {
             Expr::Pi(abs) => abs.subst(v, e, ctx),
             Expr::Lambda(abs) => abs.subst(v, e, ctx),
             Expr::App(app) => app.subst(v, e, ctx),
+            Expr::Quot(q) => q.subst(v, e, ctx),
+            Expr::Rec(r) => r.subst(v, e, ctx),
+            Expr::Proj(p) => p.e.subst(v, e, ctx),
+            Expr::Sigma(abs) => abs.subst(v, e, ctx),
+            Expr::Pair(p) => p.subst(v, e, ctx),
             Expr::Uni(_) => (),
         }
     }
@@ -56,6 +61,30 @@ impl Subst for Application {
     }
 }
 
+impl Subst for Quot {
+    fn subst(&mut self, v: &Variable, e: &Expr, ctx: &mut SubstTracker) {
+        for c in self.children_mut() {
+            c.subst(v, e, ctx);
+        }
+    }
+}
+
+impl Subst for Pair {
+    fn subst(&mut self, v: &Variable, e: &Expr, ctx: &mut SubstTracker) {
+        self.typ.subst(v, e, ctx);
+        self.fst.subst(v, e, ctx);
+        self.snd.subst(v, e, ctx);
+    }
+}
+
+impl Subst for Rec {
+    fn subst(&mut self, v: &Variable, e: &Expr, ctx: &mut SubstTracker) {
+        for (_, c) in self.fields.iter_mut() {
+            c.subst(v, e, ctx);
+        }
+    }
+}
+
 impl<T> Subst for Abstraction<T> {
     fn subst(&mut self, v: &Variable, e: &Expr, ctx: &mut SubstTracker) {
         let x = ctx.refresh_var(&self.x);
@@ -65,3 +94,15 @@ impl<T> Subst for Abstraction<T> {
         self.x = x;
     }
 }
+
+/// Replaces every `vᵢ` with `eᵢ` in `e` at once, so that `eᵢ` mentioning some
+/// `vⱼ` does not get substituted again.
+pub(crate) fn subst_all(e: &mut Expr, pairs: &[(Variable, Expr)], trk: &mut SubstTracker) {
+    let fresh: Vec<Variable> = pairs.iter().map(|(v, _)| trk.refresh_var(v)).collect();
+    for ((v, _), f) in pairs.iter().zip(&fresh) {
+        e.subst(v, &Expr::Var(f.clone()), trk);
+    }
+    for ((_, e2), f) in pairs.iter().zip(&fresh) {
+        e.subst(f, e2, trk);
+    }
+}
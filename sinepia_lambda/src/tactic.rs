@@ -0,0 +1,343 @@
+use crate::elab::Elaborator;
+use crate::unify::Unifier;
+use crate::{ast::*, Context, Error, Normalize, Subst, SubstTracker, TypeInfer, Uinf};
+use std::marker::PhantomData;
+
+/// Search depth used by [Tactic::Auto] when none is given.
+pub const AUTO_DEPTH: Uinf = 5;
+
+/// Tactics only construct proof terms, the result is still checked by
+/// [Context::extend_type] so tactics are not part of the trusted kernel.
+impl Tactic {
+    /// Runs the tactic against `goal` and returns the constructed proof.
+    pub fn run(&self, ctx: &Context, goal: &Expr, trk: &mut SubstTracker) -> Result<Expr, Error> {
+        match self {
+            Tactic::Decide => decide(ctx, goal, trk),
+            Tactic::Auto(depth) => {
+                auto(ctx, goal, depth.unwrap_or(AUTO_DEPTH), trk).ok_or(Error::ProofNotFound)
+            }
+        }
+    }
+}
+
+/// Proof by reflection.
+///
+/// Resolves an instance `d` of `Decidable G` for the goal `G`, where the
+/// class is declared along the lines of
+///```text
+///   class Decidable (P : Prop) : type 1 {
+///       holds : Bool,
+///       sound : forall (h : IsTrue holds), P
+///   }
+///```
+/// Booleans are Church encoded, `Bool = Π A: U, Π a: A, Π b: A, A` and
+/// `true = λ A: U, λ a: A, λ b: A, a`, and `IsTrue b = b Prop Unit Empty`
+/// with `Unit = Π A: Prop, Π a: A, A` and `Empty = Π A: Prop, A`. If `d.holds`
+/// evaluates to `true` the hypothesis of `d.sound` computes to `Unit`, the
+/// identity function proves it and `d.sound` applied to it proves `G`.
+///
+/// Only definitions and instances compute, so the decision procedure has to
+/// be declared with `def` rather than as theorems, which are opaque.
+fn decide(ctx: &Context, goal: &Expr, trk: &mut SubstTracker) -> Result<Expr, Error> {
+    let class = Variable::Str(StrVar {
+        name: DECIDABLE.to_owned(),
+    });
+    let wanted = Expr::App(Application {
+        e1: Box::new(Expr::Var(class.clone())),
+        e2: Box::new(goal.clone()),
+    });
+    let d = Elaborator::new(ctx.clone(), &class, trk).resolve(&wanted)?;
+    let field = |name: &str| {
+        Expr::Proj(Proj {
+            e: Box::new(d.clone()),
+            field: Variable::Str(StrVar {
+                name: name.to_owned(),
+            }),
+        })
+    };
+    let holds = field(HOLDS)
+        .normalize(ctx, trk)
+        .ok_or(Error::ExprDoesNotTypeCheck(None))?;
+    if !is_true(&holds) {
+        return Err(Error::CannotDecide(Box::new(holds)));
+    }
+    let sound = field(SOUND);
+    let typ = sound
+        .type_infer(ctx, trk)
+        .ok_or(Error::ExprDoesNotTypeCheck(None))?;
+    let abs = match typ.normalize(ctx, trk) {
+        Some(Expr::Pi(abs)) => abs,
+        _ => return Err(Error::ExprDoesNotTypeCheck(None)),
+    };
+    let h = abs
+        .t
+        .normalize(ctx, trk)
+        .ok_or(Error::ExprDoesNotTypeCheck(None))?;
+    let witness = unit_witness(&h).ok_or_else(|| Error::CannotDecide(Box::new(h.clone())))?;
+    let mut concl = abs.e.as_ref().clone();
+    concl.subst(&abs.x, &witness, trk);
+    if !ctx.types_equal(trk, goal, &concl) {
+        return Err(Error::TypesDoesNotMatch(Box::new((goal.clone(), concl))));
+    }
+    Ok(Expr::App(Application {
+        e1: Box::new(sound),
+        e2: Box::new(witness),
+    }))
+}
+
+/// Returns [true] if `b` is (alpha equivalent to) the Church encoded
+/// `λ A: Uᵢ, λ a: A, λ b: A, a`.
+fn is_true(b: &Expr) -> bool {
+    let (ty, a) = match b {
+        Expr::Lambda(ty) => match (ty.t.as_ref(), ty.e.as_ref()) {
+            (Expr::Uni(_), Expr::Lambda(a)) => (ty, a),
+            _ => return false,
+        },
+        _ => return false,
+    };
+    let b = match a.e.as_ref() {
+        Expr::Lambda(b) => b,
+        _ => return false,
+    };
+    let of_ty = |t: &Expr| matches!(t, Expr::Var(x) if x == &ty.x);
+    of_ty(&a.t) && of_ty(&b.t) && matches!(b.e.as_ref(), Expr::Var(x) if x == &a.x && x != &b.x)
+}
+
+/// If `h` is (alpha equivalent to) `Π A: Uᵢ, Π a: A, A` returns
+/// `λ A: Uᵢ, λ a: A, a`.
+fn unit_witness(h: &Expr) -> Option<Expr> {
+    let outer = match h {
+        Expr::Pi(p) => p,
+        _ => return None,
+    };
+    let inner = match (outer.t.as_ref(), outer.e.as_ref()) {
+        (Expr::Uni(_), Expr::Pi(p)) => p,
+        _ => return None,
+    };
+    match (inner.t.as_ref(), inner.e.as_ref()) {
+        (Expr::Var(a1), Expr::Var(a2)) if a1 == &outer.x && a2 == &outer.x => (),
+        _ => return None,
+    }
+    Some(Expr::Lambda(Lambda {
+        x: outer.x.clone(),
+        t: outer.t.clone(),
+        e: Box::new(Expr::Lambda(Lambda {
+            x: inner.x.clone(),
+            t: inner.t.clone(),
+            e: Box::new(Expr::Var(inner.x.clone())),
+            inst: false,
+            _ty: PhantomData,
+        })),
+        inst: false,
+        _ty: PhantomData,
+    }))
+}
+
+/// Depth limited backward search.
+///
+/// Goals of the form `Π x: A, B` are introduced as `λ x: A, _` for free,
+/// otherwise every entry `v: Π x₁: A₁, ... Π xₙ: Aₙ, C` in the context whose
+/// conclusion `C` unifies with the goal is tried. Arguments that are not
+/// determined by unification are searched for recursively with one level
+/// less of depth.
+fn auto(ctx: &Context, goal: &Expr, depth: Uinf, trk: &mut SubstTracker) -> Option<Expr> {
+    match goal.normalize(ctx, trk)? {
+        Expr::Pi(p) => {
+            let ctx2 = ctx.with_local(p.x.clone(), p.t.as_ref().clone());
+            let e = auto(&ctx2, &p.e, depth, trk)?;
+            Some(Expr::Lambda(Lambda {
+                x: p.x,
+                t: p.t,
+                e: Box::new(e),
+                inst: p.inst,
+                _ty: PhantomData,
+            }))
+        }
+        goal if depth > 0 => ctx
+            .hypotheses()
+            .find_map(|(v, typ)| apply(ctx, v, typ, &goal, depth, trk)),
+        _ => None,
+    }
+}
+
+/// Returns every proof of `goal` that consists of a single entry of the
+/// context applied to arguments that are either determined by unification
+/// or are themselves entries of the context.
+pub(crate) fn suggestions(ctx: &Context, goal: &Expr, trk: &mut SubstTracker) -> Vec<Expr> {
+    let goal = match goal.normalize(ctx, trk) {
+        Some(goal) => goal,
+        None => return vec![],
+    };
+    let mut found: Vec<Expr> = ctx
+        .hypotheses()
+        .filter_map(|(v, typ)| apply(ctx, v, typ, &goal, 2, trk))
+        .collect();
+    found.sort_by_cached_key(|e| e.to_string());
+    found
+}
+
+/// Tries to prove `goal` by applying `v: typ` to enough arguments.
+fn apply(
+    ctx: &Context,
+    v: &Variable,
+    typ: &Expr,
+    goal: &Expr,
+    depth: Uinf,
+    trk: &mut SubstTracker,
+) -> Option<Expr> {
+    let mut concl = typ.normalize(ctx, trk)?;
+    let mut binders = vec![];
+    while let Expr::Pi(p) = concl {
+        let m = trk.refresh_var(&p.x);
+        let mut e = *p.e;
+        e.subst(&p.x, &Expr::Var(m.clone()), trk);
+        binders.push((m, *p.t));
+        concl = e;
+    }
+    let mut unifier = Unifier::new(binders.iter().map(|(m, _)| m.clone()).collect());
+    if !unifier.unify(&concl, goal, trk) {
+        return None;
+    }
+    let mut proof = Expr::Var(v.clone());
+    for (m, t) in binders {
+        let arg = match unifier.lookup(&m) {
+            Some(arg) => unifier.instantiate(arg, trk),
+            None => {
+                let t = unifier.instantiate(&t, trk);
+                if unifier.has_unsolved(&t) {
+                    return None;
+                }
+                let arg = auto(ctx, &t, depth - 1, trk)?;
+                unifier.assign(m, arg.clone());
+                arg
+            }
+        };
+        proof = Expr::App(Application {
+            e1: Box::new(proof),
+            e2: Box::new(arg),
+        });
+    }
+    Some(proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+    use crate::{ast::*, Context, Error, SubstTracker};
+
+    fn def(ctx: &mut Context, trk: &mut SubstTracker, x: &str, t: Expr, e: Expr) {
+        ctx.add_def(var(x), t, e, Reducibility::Reducible, trk)
+            .unwrap();
+    }
+
+    /// Church encoded booleans, `Unit`, `Empty` and the class `Decidable`
+    /// with instances deciding `Unit` as `true` and `Empty` as `false`.
+    fn decidable() -> (Context, SubstTracker) {
+        let (mut ctx, mut trk) = axioms([]);
+        let bool_of = |a: Expr| pi("a", a.clone(), pi("b", a.clone(), a));
+        let choose = |pick: &str| lam("A", typ(0), lam("a", v("A"), lam("b", v("A"), v(pick))));
+        def(
+            &mut ctx,
+            &mut trk,
+            "Bool",
+            typ(1),
+            pi("A", typ(0), bool_of(v("A"))),
+        );
+        def(&mut ctx, &mut trk, "true", v("Bool"), choose("a"));
+        def(&mut ctx, &mut trk, "false", v("Bool"), choose("b"));
+        let unit = pi("A", prop(), pi("a", v("A"), v("A")));
+        def(&mut ctx, &mut trk, "Unit", prop(), unit);
+        def(&mut ctx, &mut trk, "Empty", prop(), pi("A", prop(), v("A")));
+        let is_true = lam("b", v("Bool"), app(v("b"), [prop(), v("Unit"), v("Empty")]));
+        def(
+            &mut ctx,
+            &mut trk,
+            "IsTrue",
+            pi("b", v("Bool"), prop()),
+            is_true,
+        );
+        let class = Record {
+            name: var(DECIDABLE),
+            params: vec![(var("P"), prop())],
+            sort: Universe::Type(1),
+            fields: vec![
+                (var(HOLDS), v("Bool")),
+                (var(SOUND), pi("h", app(v("IsTrue"), [v(HOLDS)]), v("P"))),
+            ],
+        };
+        ctx.add_class(class, &mut trk).unwrap();
+        for (inst, p, b) in [("decUnit", "Unit", "true"), ("decEmpty", "Empty", "false")] {
+            let val = Expr::Rec(Rec {
+                fields: vec![(var(HOLDS), v(b)), (var(SOUND), lam("h", v(p), v("h")))],
+            });
+            let typ = app(v(DECIDABLE), [v(p)]);
+            ctx.add_instance(var(inst), typ, val, &mut trk).unwrap();
+        }
+        (ctx, trk)
+    }
+
+    #[test]
+    fn decide_proves_propositions_decided_true() {
+        let (mut ctx, mut trk) = decidable();
+        ctx.add_theorem(var("u"), v("Unit"), &mut trk).unwrap();
+        assert!(ctx.prove_by(&var("u"), &Tactic::Decide, &mut trk).is_ok());
+    }
+
+    #[test]
+    fn decide_rejects_propositions_decided_false() {
+        let (mut ctx, mut trk) = decidable();
+        ctx.add_theorem(var("e"), v("Empty"), &mut trk).unwrap();
+        let proof = ctx.prove_by(&var("e"), &Tactic::Decide, &mut trk);
+        assert!(matches!(proof, Err(Error::CannotDecide(_))));
+        assert!(ctx.is_unproven_theory(&var("e")));
+    }
+
+    #[test]
+    fn decide_needs_an_instance() {
+        let (mut ctx, mut trk) = decidable();
+        ctx.add_axiom(var("Q"), prop(), &mut trk).unwrap();
+        ctx.add_theorem(var("q"), v("Q"), &mut trk).unwrap();
+        let proof = ctx.prove_by(&var("q"), &Tactic::Decide, &mut trk);
+        assert!(matches!(proof, Err(Error::InstanceNotFound(_))));
+    }
+
+    /// `a: A` and the implications `A → B → C`.
+    fn chain() -> (Context, SubstTracker) {
+        axioms([
+            ("A", prop()),
+            ("B", prop()),
+            ("C", prop()),
+            ("a", v("A")),
+            ("f", pi("x", v("A"), v("B"))),
+            ("g", pi("x", v("B"), v("C"))),
+        ])
+    }
+
+    #[test]
+    fn auto_chains_entries_within_the_depth() {
+        let (mut ctx, mut trk) = chain();
+        ctx.add_theorem(var("c"), v("C"), &mut trk).unwrap();
+        let proof = ctx.prove_by(&var("c"), &Tactic::Auto(None), &mut trk);
+        assert_eq!(
+            proof.unwrap().to_string(),
+            app(v("g"), [app(v("f"), [v("a")])]).to_string()
+        );
+    }
+
+    #[test]
+    fn auto_gives_up_beyond_the_depth() {
+        let (mut ctx, mut trk) = chain();
+        ctx.add_theorem(var("c"), v("C"), &mut trk).unwrap();
+        let proof = ctx.prove_by(&var("c"), &Tactic::Auto(Some(2)), &mut trk);
+        assert!(matches!(proof, Err(Error::ProofNotFound)));
+        assert!(ctx.is_unproven_theory(&var("c")));
+    }
+
+    #[test]
+    fn auto_does_not_use_the_theorem_itself() {
+        let (mut ctx, mut trk) = axioms([("A", prop())]);
+        ctx.add_theorem(var("t"), v("A"), &mut trk).unwrap();
+        let proof = ctx.prove_by(&var("t"), &Tactic::Auto(None), &mut trk);
+        assert!(matches!(proof, Err(Error::ProofNotFound)));
+    }
+}
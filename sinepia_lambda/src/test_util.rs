@@ -0,0 +1,131 @@
+//! Shorthands for writing terms in tests.
+use crate::{ast::*, Context, SubstTracker, Uinf};
+use std::marker::PhantomData;
+
+pub(crate) fn var(name: &str) -> Variable {
+    Variable::Str(StrVar {
+        name: name.to_owned(),
+    })
+}
+
+pub(crate) fn v(name: &str) -> Expr {
+    Expr::Var(var(name))
+}
+
+pub(crate) fn prop() -> Expr {
+    Expr::Uni(Universe::Prop)
+}
+
+pub(crate) fn typ(level: Uinf) -> Expr {
+    Expr::Uni(Universe::Type(level))
+}
+
+pub(crate) fn pi(x: &str, t: Expr, e: Expr) -> Expr {
+    Expr::Pi(Pi {
+        x: var(x),
+        t: Box::new(t),
+        e: Box::new(e),
+        inst: false,
+        _ty: PhantomData,
+    })
+}
+
+/// `Π [x: t], e`
+pub(crate) fn inst_pi(x: &str, t: Expr, e: Expr) -> Expr {
+    Expr::Pi(Pi {
+        x: var(x),
+        t: Box::new(t),
+        e: Box::new(e),
+        inst: true,
+        _ty: PhantomData,
+    })
+}
+
+pub(crate) fn lam(x: &str, t: Expr, e: Expr) -> Expr {
+    Expr::Lambda(Lambda {
+        x: var(x),
+        t: Box::new(t),
+        e: Box::new(e),
+        inst: false,
+        _ty: PhantomData,
+    })
+}
+
+pub(crate) fn sigma(x: &str, t: Expr, e: Expr) -> Expr {
+    Expr::Sigma(Sigma {
+        x: var(x),
+        t: Box::new(t),
+        e: Box::new(e),
+        inst: false,
+        _ty: PhantomData,
+    })
+}
+
+/// `pair typ fst snd`
+pub(crate) fn pair(typ: Expr, fst: Expr, snd: Expr) -> Expr {
+    Expr::Pair(Pair {
+        typ: Box::new(typ),
+        fst: Box::new(fst),
+        snd: Box::new(snd),
+    })
+}
+
+/// `e.f`
+pub(crate) fn proj(e: Expr, f: &str) -> Expr {
+    Expr::Proj(Proj {
+        e: Box::new(e),
+        field: var(f),
+    })
+}
+
+/// `f a₁ ... aₙ`
+pub(crate) fn app<const N: usize>(f: Expr, args: [Expr; N]) -> Expr {
+    args.into_iter().fold(f, |e1, e2| {
+        Expr::App(Application {
+            e1: Box::new(e1),
+            e2: Box::new(e2),
+        })
+    })
+}
+
+/// A context with the given axioms declared in order.
+pub(crate) fn axioms<const N: usize>(decls: [(&str, Expr); N]) -> (Context, SubstTracker) {
+    let mut ctx = Context::new();
+    let mut trk = SubstTracker::new();
+    for (x, t) in decls {
+        ctx.add_axiom(var(x), t, &mut trk).unwrap();
+    }
+    (ctx, trk)
+}
+
+/// `Quot A R`
+pub(crate) fn quot(a: Expr, r: Expr) -> Expr {
+    Expr::Quot(Quot::Type {
+        a: Box::new(a),
+        r: Box::new(r),
+    })
+}
+
+/// `Quot.mk R e`
+pub(crate) fn mk(r: Expr, e: Expr) -> Expr {
+    Expr::Quot(Quot::Mk {
+        r: Box::new(r),
+        e: Box::new(e),
+    })
+}
+
+/// `quot_sound R a b h`
+pub(crate) fn sound(r: Expr, a: Expr, b: Expr, h: Expr) -> Expr {
+    Expr::Quot(Quot::Sound {
+        r: Box::new(r),
+        a: Box::new(a),
+        b: Box::new(b),
+        h: Box::new(h),
+    })
+}
+
+/// `x = y` at type `t` as Leibniz equality.
+pub(crate) fn eq(t: Expr, x: Expr, y: Expr) -> Expr {
+    let motive = pi("z", t, prop());
+    pi("P", motive, pi("_", app(v("P"), [x]), app(v("P"), [y])))
+}
@@ -1,9 +1,10 @@
-use crate::subst::{Subst, SubstTracker};
-use crate::{ast::*, normalize::*, Context};
+use crate::subst::{subst_all, Subst, SubstTracker};
+use crate::unify::Unifier;
+use crate::{ast::*, normalize::*, unify::mentions, Context};
 use std::{cmp::max, marker::PhantomData};
 /// A data type that derives this trait can be type inferred.
 pub trait TypeInfer {
-    fn type_infer(&self, ctx: Context, trk: &mut SubstTracker) -> Option<Expr>;
+    fn type_infer(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr>;
 }
 
 ///```text
@@ -12,38 +13,45 @@ pub trait TypeInfer {
 ///           Γ |- x: A
 ///```
 impl TypeInfer for Variable {
-    fn type_infer(&self, ctx: Context, _: &mut SubstTracker) -> Option<Expr> {
+    fn type_infer(&self, ctx: &Context, _: &mut SubstTracker) -> Option<Expr> {
         ctx.lookup_type(self).cloned()
     }
 }
 ///```text
-///         ----------------
-///           Γ |- Uᵢ: Uᵢ₊₁
+///         ----------------        ----------------
+///           Γ |- Uᵢ: Uᵢ₊₁           Γ |- Prop: U₀
 ///```
 /// Note that if `Γ |- Uᵢ: Uᵢ₊₁` and `Γ |- Uᵢ: Uᵢ₊₁` then `Γ |- Uᵢ: Uᵢ₊₂` But we will only
 /// generate the smallest suitable type. This has some subtle implication. One example is in
 /// typing [Pi].
 impl TypeInfer for Universe {
-    fn type_infer(&self, _: Context, _: &mut SubstTracker) -> Option<Expr> {
-        Some(Expr::Uni(Universe {
-            level: self.level + 1,
-        }))
+    fn type_infer(&self, _: &Context, _: &mut SubstTracker) -> Option<Expr> {
+        let level = match self {
+            Universe::Prop => 0,
+            Universe::Type(level) => level + 1,
+        };
+        Some(Expr::Uni(Universe::Type(level)))
     }
 }
 ///```text
-///            Γ |- A: Uᵢ     Γ, x: A |- y: Uᵢ
-///         ------------------------------------
-///                 Γ |- (Π x: A, y): Uᵢ
+///            Γ |- A: Uᵢ     Γ, x: A |- y: Uᵢ          Γ |- A: Uᵢ     Γ, x: A |- y: Prop
+///         ------------------------------------     ---------------------------------------
+///                 Γ |- (Π x: A, y): Uᵢ                     Γ |- (Π x: A, y): Prop
 ///```
 /// Note that the implementation is is slightly different where we type
 /// `A: Uᵢ`, `Y: Uⱼ` and we pick the max, that is because if A: Uᵢ, then A: Uᵢ₊₁
-
+///
+/// The second rule makes [Universe::Prop] impredicative, quantifying over any type
+/// (including `Prop` itself) still yields a proposition.
 impl TypeInfer for Pi {
-    fn type_infer(&self, ctx: Context, trk: &mut SubstTracker) -> Option<Expr> {
-        let k1 = infer_universe(ctx.clone(), &self.t, trk)?;
-        let ctx2 = ctx.with_type(self.x.clone(), self.t.as_ref().clone());
-        let k2 = infer_universe(ctx2, &self.e, trk)?;
-        Some(Expr::Uni(max(k1, k2)))
+    fn type_infer(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        let k1 = infer_universe(ctx, &self.t, trk)?;
+        let ctx2 = ctx.with_local(self.x.clone(), self.t.as_ref().clone());
+        let k2 = infer_universe(&ctx2, &self.e, trk)?;
+        match k2 {
+            Universe::Prop => Some(Expr::Uni(Universe::Prop)),
+            k2 => Some(Expr::Uni(max(k1, k2))),
+        }
     }
 }
 ///````text
@@ -52,14 +60,15 @@ impl TypeInfer for Pi {
 ///         Γ |- (λ x : A, y) : (Π x: A, B)
 ///```
 impl TypeInfer for Lambda {
-    fn type_infer(&self, ctx: Context, trk: &mut SubstTracker) -> Option<Expr> {
-        infer_universe(ctx.clone(), &self.t, trk)?;
-        let ctx2 = ctx.with_type(self.x.clone(), self.t.as_ref().clone());
-        let te = self.e.type_infer(ctx2, trk)?;
+    fn type_infer(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        infer_universe(ctx, &self.t, trk)?;
+        let ctx2 = ctx.with_local(self.x.clone(), self.t.as_ref().clone());
+        let te = self.e.type_infer(&ctx2, trk)?;
         Some(Expr::Pi(Pi {
             x: self.x.clone(),
             t: self.t.clone(),
             e: Box::new(te),
+            inst: self.inst,
             _ty: PhantomData,
         }))
     }
@@ -71,9 +80,9 @@ impl TypeInfer for Lambda {
 ///                       Γ |- m n : Y[n/x]
 ///```
 impl TypeInfer for Application {
-    fn type_infer(&self, ctx: Context, trk: &mut SubstTracker) -> Option<Expr> {
-        let abs = infer_pi(ctx.clone(), &self.e1, trk)?;
-        let te = self.e2.type_infer(ctx.clone(), trk)?;
+    fn type_infer(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        let abs = infer_pi(ctx, &self.e1, trk)?;
+        let te = self.e2.type_infer(ctx, trk)?;
         if ctx.types_equal(trk, &abs.t, &te) {
             let mut ret = abs.e.as_ref().clone();
             ret.subst(&abs.x, &self.e2, trk);
@@ -84,32 +93,555 @@ impl TypeInfer for Application {
     }
 }
 
+///```text
+///            Γ |- A: Uᵢ     Γ, x: A |- B: Uⱼ
+///         ------------------------------------
+///             Γ |- (Σ x: A, B): max(Uᵢ, Uⱼ)
+///```
+impl TypeInfer for Sigma {
+    fn type_infer(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        let k1 = infer_universe(ctx, &self.t, trk)?;
+        let ctx2 = ctx.with_local(self.x.clone(), self.t.as_ref().clone());
+        let k2 = infer_universe(&ctx2, &self.e, trk)?;
+        Some(Expr::Uni(max(k1, k2)))
+    }
+}
+
+///```text
+///     Γ |- (Σ x: A, B): Uᵢ     Γ |- a: A     Γ |- b: B[a/x]
+///   --------------------------------------------------------
+///              Γ |- pair (Σ x: A, B) a b: Σ x: A, B
+///```
+impl TypeInfer for Pair {
+    fn type_infer(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        infer_universe(ctx, &self.typ, trk)?;
+        let s = match self.typ.normalize(ctx, trk)? {
+            Expr::Sigma(s) => s,
+            _ => return None,
+        };
+        let ta = self.fst.type_infer(ctx, trk)?;
+        let mut tb = *s.e;
+        tb.subst(&s.x, &self.fst, trk);
+        let tb2 = self.snd.type_infer(ctx, trk)?;
+        if ctx.types_equal(trk, &s.t, &ta) && ctx.types_equal(trk, &tb, &tb2) {
+            Some(self.typ.as_ref().clone())
+        } else {
+            None
+        }
+    }
+}
+
+///```text
+///       Γ |- A: Uᵢ    Γ |- R: A → A → Prop           Γ |- e: A    Γ |- R: A → A → Prop
+///     ------------------------------------        ------------------------------------
+///               Γ |- (A / R): Uᵢ                        Γ |- quot_mk R e: A / R
+///
+///     Γ |- q: A / R    Γ |- f: A → B    Γ |- h: Π x: A, Π y: A, R x y → f x = f y
+///   -------------------------------------------------------------------------------
+///                           Γ |- quot_lift f h q: B
+///
+///                                  Γ |- h: R a b
+///                -------------------------------------------------
+///                  Γ |- quot_sound R a b h: quot_mk R a = quot_mk R b
+///```
+/// Where `x = y` for `x y: T` is Leibniz equality `Π P: T → Prop, P x → P y`.
+/// If `A` is a proposition `B` has to be one too.
+impl TypeInfer for Quot {
+    fn type_infer(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        match self {
+            Quot::Type { a, r } => {
+                let k = infer_universe(ctx, a, trk)?;
+                check_relation(ctx, r, a, trk)?;
+                Some(Expr::Uni(k))
+            }
+            Quot::Mk { r, e } => {
+                let a = e.type_infer(ctx, trk)?;
+                check_relation(ctx, r, &a, trk)?;
+                Some(Expr::Quot(Quot::Type {
+                    a: Box::new(a),
+                    r: r.clone(),
+                }))
+            }
+            Quot::Lift { f, h, q } => {
+                let (a, r) = match q.type_infer(ctx, trk)?.normalize(ctx, trk)? {
+                    Expr::Quot(Quot::Type { a, r }) => (a, r),
+                    _ => return None,
+                };
+                let abs = infer_pi(ctx, f, trk)?;
+                if mentions(&abs.e, &abs.x) || !ctx.types_equal(trk, &abs.t, &a) {
+                    return None;
+                }
+                // no large elimination, a proof of a quotient proposition
+                // only gives proofs.
+                if infer_universe(ctx, &a, trk)? == Universe::Prop
+                    && infer_universe(ctx, &abs.e, trk)? != Universe::Prop
+                {
+                    return None;
+                }
+                let x = trk.refresh_var(&abs.x);
+                let y = trk.refresh_var(&abs.x);
+                let fx = app(f.as_ref().clone(), Expr::Var(x.clone()));
+                let fy = app(f.as_ref().clone(), Expr::Var(y.clone()));
+                let rx = app(r.as_ref().clone(), Expr::Var(x.clone()));
+                let rxy = app(rx, Expr::Var(y.clone()));
+                let eq = leibniz(&abs.e, fx, fy, trk);
+                let respects = pi(x, &a, pi(y, &a, arrow(rxy, eq, trk)));
+                let th = h.type_infer(ctx, trk)?;
+                if ctx.types_equal(trk, &th, &respects) {
+                    Some(*abs.e)
+                } else {
+                    None
+                }
+            }
+            Quot::Sound { r, a, b, h } => {
+                let ta = a.type_infer(ctx, trk)?;
+                check_relation(ctx, r, &ta, trk)?;
+                let tb = b.type_infer(ctx, trk)?;
+                let rab = app(
+                    app(r.as_ref().clone(), a.as_ref().clone()),
+                    b.as_ref().clone(),
+                );
+                let th = h.type_infer(ctx, trk)?;
+                if !ctx.types_equal(trk, &ta, &tb) || !ctx.types_equal(trk, &th, &rab) {
+                    return None;
+                }
+                let q = Expr::Quot(Quot::Type {
+                    a: Box::new(ta),
+                    r: r.clone(),
+                });
+                let mk = |e: &Expr| {
+                    Expr::Quot(Quot::Mk {
+                        r: r.clone(),
+                        e: Box::new(e.clone()),
+                    })
+                };
+                Some(leibniz(&q, mk(a), mk(b), trk))
+            }
+        }
+    }
+}
+
+///```text
+///     R (x₁: T₁) ... (xₙ: Tₙ) { f₁: A₁, ... fₘ: Aₘ }     Γ |- eᵢ: Aᵢ[pⱼ/xⱼ, eₖ/fₖ]
+///   -------------------------------------------------------------------------------
+///                Γ |- { f₁ := e₁, ... fₘ := eₘ }: R p₁ ... pₙ
+///```
+/// `R` is the record type whose fields are exactly `f₁ ... fₘ`, there is at
+/// most one since [Context::add_record] rejects record types with the same
+/// fields. The parameters `pⱼ` are found by unifying the declared field
+/// types with the inferred ones and then checked against the rule above.
+impl TypeInfer for Rec {
+    fn type_infer(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        let mut candidates = ctx.records().filter(|(_, rec)| {
+            rec.fields.len() == self.fields.len()
+                && rec
+                    .fields
+                    .iter()
+                    .all(|(f, _)| self.fields.iter().any(|(g, _)| f == g))
+        });
+        let (r, rec) = match (candidates.next(), candidates.next()) {
+            (Some(found), None) => found,
+            _ => return None,
+        };
+        let values: Vec<Expr> = rec
+            .fields
+            .iter()
+            .map(|(f, _)| self.fields.iter().find(|(g, _)| f == g).unwrap().1.clone())
+            .collect();
+        let mut types = Vec::with_capacity(values.len());
+        for v in &values {
+            types.push(v.type_infer(ctx, trk)?);
+        }
+        let metas: Vec<Variable> = rec.params.iter().map(|(x, _)| trk.refresh_var(x)).collect();
+        let margs: Vec<Expr> = metas.iter().cloned().map(Expr::Var).collect();
+        let mut mctx = ctx.clone();
+        for (i, m) in metas.iter().enumerate() {
+            let mut t = rec.params[i].1.clone();
+            let pairs: Vec<(Variable, Expr)> = rec.params[..i]
+                .iter()
+                .map(|(x, _)| x.clone())
+                .zip(margs.iter().cloned())
+                .collect();
+            subst_all(&mut t, &pairs, trk);
+            mctx = mctx.with_local(m.clone(), t);
+        }
+        let mut unifier = Unifier::new(metas.clone());
+        for (i, t) in types.iter().enumerate() {
+            let expected = rec
+                .field_type(i, &margs, &values[..i], trk)
+                .normalize(&mctx, trk)?;
+            let t = t.normalize(ctx, trk)?;
+            if !unifier.unify(&expected, &t, trk) {
+                return None;
+            }
+        }
+        let mut args = Vec::with_capacity(metas.len());
+        for m in &metas {
+            args.push(unifier.instantiate(unifier.lookup(m)?, trk));
+        }
+        for (i, t) in types.iter().enumerate() {
+            let expected = rec.field_type(i, &args, &values[..i], trk);
+            if !ctx.types_equal(trk, &expected, t) {
+                return None;
+            }
+        }
+        let typ = args.into_iter().fold(Expr::Var(r.clone()), app);
+        typ.type_infer(ctx, trk)?;
+        Some(typ)
+    }
+}
+
+///```text
+///     Γ |- e: R p₁ ... pₙ      R (x₁: T₁) ... (xₙ: Tₙ) { f₁: A₁, ... fₘ: Aₘ }
+///   ----------------------------------------------------------------------------
+///                     Γ |- e.fᵢ: Aᵢ[pⱼ/xⱼ, e.fₖ/fₖ]
+///
+///         Γ |- e: Σ x: A, B                 Γ |- e: Σ x: A, B
+///       ---------------------          ----------------------------
+///          Γ |- e.fst: A                 Γ |- e.snd: B[e.fst/x]
+///```
+impl TypeInfer for Proj {
+    fn type_infer(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
+        if let Some(v) = ctx.projection_function(self) {
+            return v.type_infer(ctx, trk);
+        }
+        let mut typ = self.e.type_infer(ctx, trk)?.normalize(ctx, trk)?;
+        if let Expr::Sigma(s) = typ {
+            return match &self.field {
+                Variable::Str(f) if f.name == FST => Some(*s.t),
+                Variable::Str(f) if f.name == SND => {
+                    let fst = Expr::Proj(Proj {
+                        e: self.e.clone(),
+                        field: Variable::Str(StrVar {
+                            name: FST.to_owned(),
+                        }),
+                    });
+                    let mut b = *s.e;
+                    b.subst(&s.x, &fst, trk);
+                    Some(b)
+                }
+                _ => None,
+            };
+        }
+        let mut args = vec![];
+        while let Expr::App(a) = typ {
+            args.push(*a.e2);
+            typ = *a.e1;
+        }
+        args.reverse();
+        let rec = match &typ {
+            Expr::Var(r) => ctx.lookup_record(r)?,
+            _ => return None,
+        };
+        let i = rec.fields.iter().position(|(f, _)| f == &self.field)?;
+        if args.len() != rec.params.len() {
+            return None;
+        }
+        let earlier: Vec<Expr> = rec.fields[..i]
+            .iter()
+            .map(|(f, _)| {
+                Expr::Proj(Proj {
+                    e: self.e.clone(),
+                    field: f.clone(),
+                })
+            })
+            .collect();
+        Some(rec.field_type(i, &args, &earlier, trk))
+    }
+}
+
 /// This is thin abstraction layer, no logic here.
 impl TypeInfer for Expr {
-    fn type_infer(&self, ctx: Context, trk: &mut SubstTracker) -> Option<Expr> {
+    fn type_infer(&self, ctx: &Context, trk: &mut SubstTracker) -> Option<Expr> {
         match self {
             Expr::Var(v) => v.type_infer(ctx, trk),
             Expr::Uni(u) => u.type_infer(ctx, trk),
             Expr::Pi(p) => p.type_infer(ctx, trk),
             Expr::Lambda(l) => l.type_infer(ctx, trk),
             Expr::App(a) => a.type_infer(ctx, trk),
+            Expr::Quot(q) => q.type_infer(ctx, trk),
+            Expr::Rec(r) => r.type_infer(ctx, trk),
+            Expr::Proj(p) => p.type_infer(ctx, trk),
+            Expr::Sigma(s) => s.type_infer(ctx, trk),
+            Expr::Pair(p) => p.type_infer(ctx, trk),
         }
     }
 }
 
-fn infer_universe(ctx: Context, e: &Expr, trk: &mut SubstTracker) -> Option<Universe> {
-    let u = e.type_infer(ctx.clone(), trk)?;
-    if let Expr::Uni(normalized) = u.normalize(&ctx, trk)? {
+pub(crate) fn infer_universe(ctx: &Context, e: &Expr, trk: &mut SubstTracker) -> Option<Universe> {
+    let u = e.type_infer(ctx, trk)?;
+    if let Expr::Uni(normalized) = u.normalize(ctx, trk)? {
         Some(normalized)
     } else {
         None
     }
 }
-fn infer_pi(ctx: Context, e: &Expr, trk: &mut SubstTracker) -> Option<Pi> {
-    let p = e.type_infer(ctx.clone(), trk)?;
-    if let Expr::Pi(normalized) = p.normalize(&ctx, trk)? {
+fn infer_pi(ctx: &Context, e: &Expr, trk: &mut SubstTracker) -> Option<Pi> {
+    let p = e.type_infer(ctx, trk)?;
+    if let Expr::Pi(normalized) = p.normalize(ctx, trk)? {
         Some(normalized)
     } else {
         None
     }
 }
+
+/// Checks that `r: A → A → Prop`.
+fn check_relation(ctx: &Context, r: &Expr, a: &Expr, trk: &mut SubstTracker) -> Option<()> {
+    let tr = r.type_infer(ctx, trk)?;
+    let prop = Expr::Uni(Universe::Prop);
+    let expected = arrow(a.clone(), arrow(a.clone(), prop, trk), trk);
+    if ctx.types_equal(trk, &tr, &expected) {
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// `x = y` as `Π P: T → Prop, P x → P y`.
+fn leibniz(t: &Expr, x: Expr, y: Expr, trk: &mut SubstTracker) -> Expr {
+    let p = fresh("P", trk);
+    let prop = Expr::Uni(Universe::Prop);
+    let px = app(Expr::Var(p.clone()), x);
+    let py = app(Expr::Var(p.clone()), y);
+    let motive = arrow(t.clone(), prop, trk);
+    pi(p, &motive, arrow(px, py, trk))
+}
+
+/// `A → B` as a [Pi] whose bound variable is fresh.
+fn arrow(a: Expr, b: Expr, trk: &mut SubstTracker) -> Expr {
+    pi(fresh("_", trk), &a, b)
+}
+
+fn fresh(name: &str, trk: &mut SubstTracker) -> Variable {
+    let name = name.to_owned();
+    trk.refresh_var(&Variable::Str(StrVar { name }))
+}
+
+fn pi(x: Variable, t: &Expr, e: Expr) -> Expr {
+    Expr::Pi(Pi {
+        x,
+        t: Box::new(t.clone()),
+        e: Box::new(e),
+        inst: false,
+        _ty: PhantomData,
+    })
+}
+
+fn app(e1: Expr, e2: Expr) -> Expr {
+    Expr::App(Application {
+        e1: Box::new(e1),
+        e2: Box::new(e2),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+    use crate::{ast::*, Context, SubstTracker, TypeInfer};
+
+    fn has_type(ctx: &Context, e: &Expr, t: &Expr, trk: &mut SubstTracker) -> bool {
+        match e.type_infer(ctx, trk) {
+            Some(inferred) => ctx.types_equal(trk, &inferred, t),
+            None => false,
+        }
+    }
+
+    #[test]
+    fn prop_lives_in_the_first_universe() {
+        let (ctx, mut trk) = axioms([]);
+        assert!(has_type(&ctx, &prop(), &typ(0), &mut trk));
+    }
+
+    #[test]
+    fn quantifying_over_propositions_is_a_proposition() {
+        let (ctx, mut trk) = axioms([]);
+        let e = pi("A", prop(), pi("a", v("A"), v("A")));
+        assert!(has_type(&ctx, &e, &prop(), &mut trk));
+    }
+
+    #[test]
+    fn quantifying_over_any_type_into_prop_is_a_proposition() {
+        let (ctx, mut trk) = axioms([("P", prop())]);
+        let e = pi("T", typ(3), pi("x", v("T"), v("P")));
+        assert!(has_type(&ctx, &e, &prop(), &mut trk));
+    }
+
+    #[test]
+    fn quantifying_into_a_type_stays_predicative() {
+        let (ctx, mut trk) = axioms([]);
+        let e = pi("A", typ(0), pi("a", v("A"), v("A")));
+        assert!(has_type(&ctx, &e, &typ(1), &mut trk));
+        assert!(!has_type(&ctx, &e, &prop(), &mut trk));
+    }
+
+    #[test]
+    fn polymorphic_identity_applies_to_its_own_type() {
+        let id_typ = pi("A", prop(), pi("a", v("A"), v("A")));
+        let (ctx, mut trk) = axioms([("id", id_typ.clone())]);
+        let e = app(v("id"), [id_typ.clone(), v("id")]);
+        assert!(has_type(&ctx, &e, &id_typ, &mut trk));
+    }
+
+    fn lift(f: Expr, h: Expr, q: Expr) -> Expr {
+        Expr::Quot(Quot::Lift {
+            f: Box::new(f),
+            h: Box::new(h),
+            q: Box::new(q),
+        })
+    }
+
+    /// `A`, a relation `R` on it with `R a b`, and `g: A → B` respecting `R`.
+    fn quotient() -> (Context, SubstTracker) {
+        let respects = pi(
+            "x",
+            v("A"),
+            pi(
+                "y",
+                v("A"),
+                pi(
+                    "h",
+                    app(v("R"), [v("x"), v("y")]),
+                    eq(v("B"), app(v("g"), [v("x")]), app(v("g"), [v("y")])),
+                ),
+            ),
+        );
+        axioms([
+            ("A", typ(0)),
+            ("R", pi("x", v("A"), pi("y", v("A"), prop()))),
+            ("a", v("A")),
+            ("b", v("A")),
+            ("r", app(v("R"), [v("a"), v("b")])),
+            ("B", typ(0)),
+            ("g", pi("x", v("A"), v("B"))),
+            ("gr", respects),
+        ])
+    }
+
+    #[test]
+    fn quotient_types_live_in_the_universe_of_the_carrier() {
+        let (ctx, mut trk) = quotient();
+        let q = quot(v("A"), v("R"));
+        assert!(has_type(&ctx, &q, &typ(0), &mut trk));
+    }
+
+    #[test]
+    fn quotient_needs_a_relation() {
+        let (ctx, mut trk) = quotient();
+        let q = quot(v("A"), v("g"));
+        assert!(q.type_infer(&ctx, &mut trk).is_none());
+    }
+
+    #[test]
+    fn classes_belong_to_the_quotient() {
+        let (ctx, mut trk) = quotient();
+        let q = quot(v("A"), v("R"));
+        assert!(has_type(&ctx, &mk(v("R"), v("a")), &q, &mut trk));
+    }
+
+    #[test]
+    fn related_elements_have_equal_classes() {
+        let (ctx, mut trk) = quotient();
+        let q = quot(v("A"), v("R"));
+        let eq_ab = eq(q.clone(), mk(v("R"), v("a")), mk(v("R"), v("b")));
+        let eq_ba = eq(q, mk(v("R"), v("b")), mk(v("R"), v("a")));
+        let s = sound(v("R"), v("a"), v("b"), v("r"));
+        assert!(has_type(&ctx, &s, &eq_ab, &mut trk));
+        assert!(!has_type(&ctx, &s, &eq_ba, &mut trk));
+    }
+
+    #[test]
+    fn lifting_computes_on_classes() {
+        let (ctx, mut trk) = quotient();
+        let lifted = lift(v("g"), v("gr"), mk(v("R"), v("a")));
+        assert!(has_type(&ctx, &lifted, &v("B"), &mut trk));
+        assert!(ctx.types_equal(&mut trk, &lifted, &app(v("g"), [v("a")])));
+        assert!(!ctx.types_equal(&mut trk, &lifted, &app(v("g"), [v("b")])));
+    }
+
+    #[test]
+    fn lifting_needs_a_function_respecting_the_relation() {
+        let (ctx, mut trk) = quotient();
+        let lifted = lift(v("g"), v("g"), mk(v("R"), v("a")));
+        assert!(lifted.type_infer(&ctx, &mut trk).is_none());
+    }
+
+    /// A relation `S` on a proposition `P` with a proof `p`, and functions
+    /// `gB: P → B` into a type and `gC: P → C` into a proposition, both
+    /// respecting `S`.
+    fn prop_quotient() -> (Context, SubstTracker) {
+        let respects = |g: &str, t: &str| {
+            let gx = app(v(g), [v("x")]);
+            let gy = app(v(g), [v("y")]);
+            let sxy = app(v("S"), [v("x"), v("y")]);
+            pi("x", v("P"), pi("y", v("P"), pi("h", sxy, eq(v(t), gx, gy))))
+        };
+        axioms([
+            ("P", prop()),
+            ("S", pi("x", v("P"), pi("y", v("P"), prop()))),
+            ("p", v("P")),
+            ("B", typ(0)),
+            ("C", prop()),
+            ("gB", pi("x", v("P"), v("B"))),
+            ("gC", pi("x", v("P"), v("C"))),
+            ("gBr", respects("gB", "B")),
+            ("gCr", respects("gC", "C")),
+        ])
+    }
+
+    #[test]
+    fn quotient_propositions_only_lift_into_propositions() {
+        let (ctx, mut trk) = prop_quotient();
+        let q = quot(v("P"), v("S"));
+        assert!(has_type(&ctx, &q, &prop(), &mut trk));
+        let into_prop = lift(v("gC"), v("gCr"), mk(v("S"), v("p")));
+        assert!(has_type(&ctx, &into_prop, &v("C"), &mut trk));
+        let into_type = lift(v("gB"), v("gBr"), mk(v("S"), v("p")));
+        assert!(into_type.type_infer(&ctx, &mut trk).is_none());
+    }
+
+    /// `s: Σ x: A, B x` and a pair `pair (Σ x: A, B x) a ba`.
+    fn dependent_pair() -> (Context, SubstTracker, Expr) {
+        let bx = |x: &str| app(v("B"), [v(x)]);
+        let s = sigma("x", v("A"), bx("x"));
+        let (ctx, trk) = axioms([
+            ("A", typ(0)),
+            ("B", pi("x", v("A"), typ(0))),
+            ("a", v("A")),
+            ("ba", bx("a")),
+            ("s", s.clone()),
+        ]);
+        (ctx, trk, pair(s, v("a"), v("ba")))
+    }
+
+    #[test]
+    fn pair_projections_compute() {
+        let (ctx, mut trk, p) = dependent_pair();
+        assert!(ctx.types_equal(&mut trk, &proj(p.clone(), FST), &v("a")));
+        assert!(ctx.types_equal(&mut trk, &proj(p, SND), &v("ba")));
+    }
+
+    #[test]
+    fn the_second_projection_depends_on_the_first() {
+        let (ctx, mut trk, _) = dependent_pair();
+        let b_fst = app(v("B"), [proj(v("s"), FST)]);
+        assert!(has_type(&ctx, &proj(v("s"), FST), &v("A"), &mut trk));
+        assert!(has_type(&ctx, &proj(v("s"), SND), &b_fst, &mut trk));
+        assert!(!has_type(
+            &ctx,
+            &proj(v("s"), SND),
+            &app(v("B"), [v("a")]),
+            &mut trk
+        ));
+    }
+
+    #[test]
+    fn pairs_are_equal_to_their_eta_expansion() {
+        let (ctx, mut trk, _) = dependent_pair();
+        let s = sigma("x", v("A"), app(v("B"), [v("x")]));
+        let eta = pair(s.clone(), proj(v("s"), FST), proj(v("s"), SND));
+        assert!(ctx.types_equal(&mut trk, &v("s"), &eta));
+        assert!(ctx.types_equal(&mut trk, &eta, &v("s")));
+        let other = pair(s, v("a"), v("ba"));
+        assert!(!ctx.types_equal(&mut trk, &v("s"), &other));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{ast::*, Subst, SubstTracker};
+use std::{collections::HashMap, mem::discriminant};
+
+/// First order unification of normalized expressions.
+///
+/// Metavariables are plain [Variable]s that are registered via
+/// [Unifier::new], every other variable is treated as a constant.
+#[derive(Clone)]
+pub(crate) struct Unifier {
+    metas: Vec<Variable>,
+    solution: HashMap<Variable, Expr>,
+}
+
+impl Unifier {
+    pub(crate) fn new(metas: Vec<Variable>) -> Self {
+        Unifier {
+            metas,
+            solution: Default::default(),
+        }
+    }
+    /// Returns the value assigned to `m` so far if any.
+    pub(crate) fn lookup(&self, m: &Variable) -> Option<&Expr> {
+        self.solution.get(m)
+    }
+    /// Assigns `e` to the metavariable `m`.
+    pub(crate) fn assign(&mut self, m: Variable, e: Expr) {
+        self.solution.insert(m, e);
+    }
+    /// Returns [true] if `e` still mentions metavariables without solutions.
+    pub(crate) fn has_unsolved(&self, e: &Expr) -> bool {
+        self.metas
+            .iter()
+            .any(|m| !self.solution.contains_key(m) && mentions(e, m))
+    }
+    /// Replaces every solved metavariable in `e` with its solution.
+    pub(crate) fn instantiate(&self, e: &Expr, trk: &mut SubstTracker) -> Expr {
+        let mut e = e.clone();
+        // solutions may mention other metavariables so we keep going
+        // until nothing changes, occurs check guarantees termination.
+        while let Some(m) = self
+            .metas
+            .iter()
+            .find(|m| self.solution.contains_key(*m) && mentions(&e, m))
+        {
+            e.subst(m, &self.solution[m], trk);
+        }
+        e
+    }
+    /// Tries to make `e1` and `e2` syntactically equal (up to alpha
+    /// equivalence) by assigning metavariables, both expressions are
+    /// assumed to be normalized. On failure some metavariables might have
+    /// been assigned already.
+    pub(crate) fn unify(&mut self, e1: &Expr, e2: &Expr, trk: &mut SubstTracker) -> bool {
+        self.unify_under(e1, e2, &mut vec![], trk)
+    }
+    fn is_meta(&self, v: &Variable) -> bool {
+        self.metas.contains(v)
+    }
+    fn unify_under(
+        &mut self,
+        e1: &Expr,
+        e2: &Expr,
+        bound: &mut Vec<Variable>,
+        trk: &mut SubstTracker,
+    ) -> bool {
+        match (e1, e2) {
+            (Expr::Var(m), e) | (e, Expr::Var(m)) if self.is_meta(m) => {
+                if let Some(s) = self.solution.get(m) {
+                    let s = s.clone();
+                    return self.unify_under(&s, e, bound, trk);
+                }
+                if matches!(e, Expr::Var(v) if v == m) {
+                    return true;
+                }
+                let e = self.instantiate(e, trk);
+                if mentions(&e, m) || bound.iter().any(|b| mentions(&e, b)) {
+                    return false;
+                }
+                self.solution.insert(m.clone(), e);
+                true
+            }
+            (Expr::Var(v1), Expr::Var(v2)) => v1 == v2,
+            (Expr::Uni(u1), Expr::Uni(u2)) => u1 == u2,
+            (
+                Expr::App(Application { e1: e11, e2: e12 }),
+                Expr::App(Application { e1: e21, e2: e22 }),
+            ) => self.unify_under(e11, e21, bound, trk) && self.unify_under(e12, e22, bound, trk),
+            (Expr::Lambda(l1), Expr::Lambda(l2)) => self.unify_abs(l1, l2, bound, trk),
+            (Expr::Pi(p1), Expr::Pi(p2)) => self.unify_abs(p1, p2, bound, trk),
+            (Expr::Sigma(s1), Expr::Sigma(s2)) => self.unify_abs(s1, s2, bound, trk),
+            (Expr::Pair(p1), Expr::Pair(p2)) => {
+                self.unify_under(&p1.fst, &p2.fst, bound, trk)
+                    && self.unify_under(&p1.snd, &p2.snd, bound, trk)
+            }
+            (Expr::Quot(q1), Expr::Quot(q2)) if discriminant(q1) == discriminant(q2) => q1
+                .children()
+                .into_iter()
+                .zip(q2.children())
+                .all(|(c1, c2)| self.unify_under(c1, c2, bound, trk)),
+            (Expr::Rec(r1), Expr::Rec(r2)) => {
+                r1.fields.len() == r2.fields.len()
+                    && r1.fields.iter().all(|(f, c1)| {
+                        match r2.fields.iter().find(|(g, _)| f == g) {
+                            Some((_, c2)) => self.unify_under(c1, c2, bound, trk),
+                            None => false,
+                        }
+                    })
+            }
+            (Expr::Proj(p1), Expr::Proj(p2)) => {
+                p1.field == p2.field && self.unify_under(&p1.e, &p2.e, bound, trk)
+            }
+            (_, _) => false,
+        }
+    }
+    fn unify_abs<T>(
+        &mut self,
+        a1: &Abstraction<T>,
+        a2: &Abstraction<T>,
+        bound: &mut Vec<Variable>,
+        trk: &mut SubstTracker,
+    ) -> bool {
+        if !self.unify_under(&a1.t, &a2.t, bound, trk) {
+            return false;
+        }
+        let mut e2 = a2.e.clone();
+        e2.subst(&a2.x, &Expr::Var(a1.x.clone()), trk);
+        bound.push(a1.x.clone());
+        let result = self.unify_under(&a1.e, &e2, bound, trk);
+        bound.pop();
+        result
+    }
+}
+
+/// Returns [true] if `v` occurs free in `e`.
+pub(crate) fn mentions(e: &Expr, v: &Variable) -> bool {
+    match e {
+        Expr::Var(v2) => v == v2,
+        Expr::Uni(_) => false,
+        Expr::App(a) => mentions(&a.e1, v) || mentions(&a.e2, v),
+        Expr::Pi(p) => mentions(&p.t, v) || (&p.x != v && mentions(&p.e, v)),
+        Expr::Lambda(l) => mentions(&l.t, v) || (&l.x != v && mentions(&l.e, v)),
+        Expr::Quot(q) => q.children().into_iter().any(|c| mentions(c, v)),
+        Expr::Rec(r) => r.fields.iter().any(|(_, c)| mentions(c, v)),
+        Expr::Proj(p) => mentions(&p.e, v),
+        Expr::Sigma(s) => mentions(&s.t, v) || (&s.x != v && mentions(&s.e, v)),
+        Expr::Pair(p) => mentions(&p.typ, v) || mentions(&p.fst, v) || mentions(&p.snd, v),
+    }
+}
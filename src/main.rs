@@ -1,22 +1,58 @@
 #[macro_use]
 extern crate lalrpop_util;
 
-use sinepia_lambda::{ast::*, Context, Error as SError, SubstTracker};
+use sinepia_lambda::{
+    ast::*, Checkpoint, Context, Elaborated, Error as SError, Location, SubstTracker,
+};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
 lalrpop_mod!(#[allow(clippy::all)] pub parser);
-use parser::StmtParser;
+use parser::{ExprParser, StmtParser, VarParser};
+
+/// Where the lines being processed come from.
+#[derive(Default)]
+struct Source {
+    /// [None] for lines typed interactively.
+    file: Option<String>,
+    line: usize,
+    /// `///` lines documenting the next declaration.
+    doc: Vec<String>,
+}
+
+/// How results are printed.
+#[derive(Default)]
+struct Options {
+    /// Whether coercions inserted by the elaborator are printed.
+    show_coercions: bool,
+}
+
 fn main() {
     let mut ctx = Context::new();
     let mut trk = SubstTracker::new();
     let mut rl = Editor::<()>::new();
+    let mut src = Source::default();
+    let mut opts = Options::default();
+    // The context before each line that changed it, for :undo.
+    let mut history: Vec<Checkpoint> = vec![];
     loop {
         let readline = rl.readline(">> ");
         match readline {
             Ok(line) => {
                 rl.add_history_entry(&line);
-                process(line, &mut ctx, &mut trk);
+                src.line += 1;
+                if line.trim() == ":undo" {
+                    match history.pop() {
+                        Some(cp) => ctx.rollback(cp),
+                        None => println!("Nothing to undo"),
+                    }
+                    continue;
+                }
+                let before = ctx.checkpoint();
+                process(line, &mut ctx, &mut trk, &mut src, &mut opts);
+                if ctx.changed_since(&before) {
+                    history.push(before);
+                }
             }
             Err(ReadlineError::Interrupted) => {
                 println!("CTRL-C");
@@ -33,37 +69,481 @@ fn main() {
         }
     }
 }
-fn process(line: String, ctx: &mut Context, trk: &mut SubstTracker) {
+/// Returns [false] if `line` is a statement or command that failed.
+fn process(
+    line: String,
+    ctx: &mut Context,
+    trk: &mut SubstTracker,
+    src: &mut Source,
+    opts: &mut Options,
+) -> bool {
+    if line.trim().is_empty() {
+        return true;
+    }
+    if let Some(doc) = line.strip_prefix("///") {
+        src.doc.push(doc.trim().to_owned());
+        return true;
+    }
+    if let Some(cmd) = line.strip_prefix(':') {
+        return process_command(cmd, ctx, trk, opts);
+    }
     let stmt = match StmtParser::new().parse(&line) {
         Ok(s) => s,
         Err(e) => {
             eprintln!("{:#}", e);
-            return;
+            src.doc.clear();
+            return false;
         }
     };
+    let name = declared_name(&stmt);
+    let before = ctx.checkpoint();
+    let result = match stmt {
+        Statement::Axiom(a) => process_axiom(a, ctx, trk, opts),
+        Statement::TH(ut) => process_unproven(ut, ctx, trk, opts),
+        Statement::THPF(th) => process_theorem_proof(th, ctx, trk, opts),
+        Statement::PF(pt) => process_proven(pt, ctx, trk, opts),
+        Statement::Reprove(pt) => process_reprove(pt, ctx, trk, opts),
+        Statement::By(tp) => process_tactic(tp, ctx, trk, opts),
+        Statement::Record(rec) => process_record(rec, ctx, trk),
+        Statement::Class(rec) => process_class(rec, ctx, trk),
+        Statement::Instance(inst) => process_instance(inst, ctx, trk, opts),
+        Statement::Coercion(v) => process_coercion(v, ctx, trk),
+        Statement::Def(def) => process_def(def, ctx, trk, opts),
+        Statement::Attribute(attr) => process_attribute(attr, ctx),
+    };
+    if let Err(e) = result {
+        display(e);
+        src.doc.clear();
+        return false;
+    }
+    let added: Vec<Variable> = ctx
+        .declared_since(&before)
+        .map(|d| d.name.clone())
+        .collect();
+    for v in added {
+        let location = Location {
+            file: src.file.clone(),
+            line: src.line,
+        };
+        ctx.set_location(&v, location)
+            .expect("the declaration was just added");
+        if Some(&v) == name.as_ref() && !src.doc.is_empty() {
+            ctx.set_doc(&v, src.doc.join("\n"))
+                .expect("the declaration was just added");
+        }
+    }
+    src.doc.clear();
+    true
+}
+
+/// Returns the name of the declaration `stmt` makes if any.
+fn declared_name(stmt: &Statement) -> Option<Variable> {
     match stmt {
-        Statement::Axiom(a) => process_axiom(a, ctx, trk),
-        Statement::TH(ut) => process_unproven(ut, ctx, trk),
-        Statement::PF(pt) => process_proven(pt, ctx, trk),
+        Statement::Axiom(a) => Some(a.name.clone()),
+        Statement::TH(th) => Some(th.name.clone()),
+        Statement::THPF(th) => Some(th.name.clone()),
+        Statement::Record(rec) | Statement::Class(rec) => Some(rec.name.clone()),
+        Statement::Instance(inst) => Some(inst.name.clone()),
+        Statement::Def(def) => Some(def.name.clone()),
+        _ => None,
     }
 }
 
-fn process_axiom(ax: Axiom, ctx: &mut Context, trk: &mut SubstTracker) {
-    if let Err(e) = ctx.add_axiom(ax.name, ax.typ, trk) {
-        display(e);
+/// Returns [false] if the command failed.
+fn process_command(
+    cmd: &str,
+    ctx: &mut Context,
+    trk: &mut SubstTracker,
+    opts: &mut Options,
+) -> bool {
+    let (name, arg) = cmd.split_once(' ').unwrap_or((cmd, ""));
+    match name {
+        "search" => process_search(arg, ctx, trk, opts),
+        "exact?" => process_suggest(arg, ctx, trk, opts),
+        "coercions" => process_show_coercions(arg, opts),
+        "deps" => process_deps(arg, ctx),
+        "print" => process_print(arg, ctx),
+        "list" => process_list(arg, ctx, opts),
+        "save" => process_save(arg, ctx),
+        "load" => process_load(arg, ctx, trk, opts),
+        _ => {
+            println!("Unknown command :{}", name);
+            false
+        }
     }
 }
 
-fn process_unproven(th: Theorem, ctx: &mut Context, trk: &mut SubstTracker) {
-    if let Err(e) = ctx.add_theorem(th.name, th.typ, trk) {
-        display(e);
+fn process_show_coercions(arg: &str, opts: &mut Options) -> bool {
+    match arg {
+        "on" => opts.show_coercions = true,
+        "off" => opts.show_coercions = false,
+        _ => {
+            println!("Expected :coercions on or :coercions off");
+            return false;
+        }
     }
+    true
 }
 
-fn process_proven(pf: Proof, ctx: &mut Context, trk: &mut SubstTracker) {
-    if let Err(e) = ctx.extend_type(&pf.name, pf.value, trk) {
-        display(e);
+/// Displays `e` hiding the inserted coercions unless asked otherwise.
+fn pretty(ctx: &Context, e: &Expr, opts: &Options) -> String {
+    ctx.pretty(e, opts.show_coercions).to_string()
+}
+
+fn process_search(arg: &str, ctx: &Context, trk: &mut SubstTracker, opts: &Options) -> bool {
+    let pattern = match ExprParser::new().parse(arg) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            return false;
+        }
+    };
+    for (v, typ) in ctx.search_by_type(&pattern, trk) {
+        println!("{} : {}", v, pretty(ctx, &typ, opts));
     }
+    true
+}
+
+fn process_suggest(arg: &str, ctx: &Context, trk: &mut SubstTracker, opts: &Options) -> bool {
+    let v = match VarParser::new().parse(arg) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            return false;
+        }
+    };
+    match ctx.suggest_proofs(&v, trk) {
+        Ok(pfs) => pfs
+            .iter()
+            .for_each(|pf| println!("{} = {}", v, pretty(ctx, pf, opts))),
+        Err(e) => {
+            display(e);
+            return false;
+        }
+    }
+    true
+}
+
+fn process_deps(arg: &str, ctx: &Context) -> bool {
+    let v = match VarParser::new().parse(arg) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            return false;
+        }
+    };
+    match ctx.dependencies(&v) {
+        Some(deps) => deps.iter().for_each(|d| println!("{}", d)),
+        None => {
+            display(SError::VariableNotFound);
+            return false;
+        }
+    }
+    true
+}
+
+fn process_list(arg: &str, ctx: &Context, opts: &Options) -> bool {
+    let decls: Vec<_> = match arg {
+        "" => ctx.declarations().collect(),
+        "axioms" => ctx.axioms().collect(),
+        "theorems" => ctx.theorems().collect(),
+        "defs" => ctx.definitions().collect(),
+        _ => {
+            println!("Expected :list, :list axioms, :list theorems or :list defs");
+            return false;
+        }
+    };
+    for d in decls {
+        if let Some(doc) = &d.meta.doc {
+            doc.lines().for_each(|l| println!("/// {}", l));
+        }
+        print!("{} {} : {}", d.meta.kind, d.name, pretty(ctx, d.typ, opts));
+        match &d.meta.location {
+            Some(Location {
+                file: Some(f),
+                line,
+            }) => println!("  -- {}:{}", f, line),
+            Some(Location { file: None, line }) => println!("  -- line {}", line),
+            None => println!(),
+        }
+    }
+    true
+}
+
+fn process_save(arg: &str, ctx: &Context) -> bool {
+    if arg.is_empty() {
+        println!("Expected :save <file>");
+        return false;
+    }
+    if let Err(e) = std::fs::write(arg, ctx.serialize()) {
+        println!("Cannot write {}: {}", arg, e);
+        return false;
+    }
+    true
+}
+
+/// Checks every line of the file `path`, if one fails nothing from the file
+/// is kept and [false] is returned.
+fn process_load(path: &str, ctx: &mut Context, trk: &mut SubstTracker, opts: &mut Options) -> bool {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("Cannot read {}: {}", path, e);
+            return false;
+        }
+    };
+    let mut src = Source {
+        file: Some(path.to_owned()),
+        ..Default::default()
+    };
+    let cp = ctx.checkpoint();
+    for line in text.lines() {
+        src.line += 1;
+        // A file is undone as a whole, not line by line.
+        let ok = if line.trim() == ":undo" {
+            println!("{}:{}: :undo cannot be used in a file", path, src.line);
+            false
+        } else {
+            process(line.to_owned(), ctx, trk, &mut src, opts)
+        };
+        if !ok {
+            println!(
+                "{}:{}: failed, nothing from the file was added",
+                path, src.line
+            );
+            ctx.rollback(cp);
+            return false;
+        }
+    }
+    true
+}
+
+fn process_print(arg: &str, ctx: &Context) -> bool {
+    let (what, arg) = arg.split_once(' ').unwrap_or((arg, ""));
+    if what != "axioms" {
+        println!("Unknown command :print {}", what);
+        return false;
+    }
+    let v = match VarParser::new().parse(arg) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            return false;
+        }
+    };
+    match ctx.axioms_used(&v) {
+        Some(axioms) if axioms.is_empty() => println!("{} does not depend on any axioms", v),
+        Some(axioms) => {
+            println!("{} depends on axioms:", v);
+            for a in axioms {
+                if ctx.is_unproven_theory(&a) {
+                    println!("{} (sorry)", a);
+                } else if a.to_string() == QUOT_SOUND {
+                    println!("{} (built in)", a);
+                } else {
+                    println!("{}", a);
+                }
+            }
+        }
+        None => {
+            display(SError::VariableNotFound);
+            return false;
+        }
+    }
+    true
+}
+
+fn process_axiom(
+    ax: Axiom,
+    ctx: &mut Context,
+    trk: &mut SubstTracker,
+    opts: &Options,
+) -> Result<(), SError> {
+    let name = ax.name.clone();
+    elaborated(
+        ctx,
+        trk,
+        opts,
+        |ctx, trk| ctx.elaborate_type(&name, &ax.typ, trk),
+        |ctx, typ, trk| ctx.add_axiom(ax.name, typ, trk),
+    )
+}
+
+fn process_unproven(
+    th: Theorem,
+    ctx: &mut Context,
+    trk: &mut SubstTracker,
+    opts: &Options,
+) -> Result<(), SError> {
+    let name = th.name.clone();
+    elaborated(
+        ctx,
+        trk,
+        opts,
+        |ctx, trk| ctx.elaborate_type(&name, &th.typ, trk),
+        |ctx, typ, trk| ctx.add_theorem(th.name, typ, trk),
+    )
+}
+
+fn process_theorem_proof(
+    th: ProvedTheorem,
+    ctx: &mut Context,
+    trk: &mut SubstTracker,
+    opts: &Options,
+) -> Result<(), SError> {
+    let name = th.name.clone();
+    elaborated(
+        ctx,
+        trk,
+        opts,
+        |ctx, trk| ctx.elaborate_type(&name, &th.typ, trk),
+        |ctx, typ, trk| {
+            let value = ctx.elaborate(&th.name, &th.value, Some(&typ), trk)?;
+            ctx.add_proved_theorem(th.name, typ, value.expr, trk)?;
+            for (o, otyp) in value.obligations {
+                println!("theorem {} : {}", o, pretty(ctx, &otyp, opts));
+            }
+            Ok(())
+        },
+    )
+}
+
+fn process_proven(
+    pf: Proof,
+    ctx: &mut Context,
+    trk: &mut SubstTracker,
+    opts: &Options,
+) -> Result<(), SError> {
+    let goal = ctx.lookup_type(&pf.name).cloned();
+    elaborated(
+        ctx,
+        trk,
+        opts,
+        |ctx, trk| ctx.elaborate(&pf.name, &pf.value, goal.as_ref(), trk),
+        |ctx, value, trk| ctx.extend_type(&pf.name, value, trk),
+    )
+}
+
+fn process_reprove(
+    pf: Proof,
+    ctx: &mut Context,
+    trk: &mut SubstTracker,
+    opts: &Options,
+) -> Result<(), SError> {
+    let goal = ctx.lookup_type(&pf.name).cloned();
+    elaborated(
+        ctx,
+        trk,
+        opts,
+        |ctx, trk| ctx.elaborate(&pf.name, &pf.value, goal.as_ref(), trk),
+        |ctx, value, trk| ctx.reprove(&pf.name, value, trk).map(|_| ()),
+    )
+}
+
+/// Elaborates using `elab` and hands the result to `f`. Nothing, not even
+/// the proof obligations, is added to `ctx` unless `f` succeeds.
+fn elaborated(
+    ctx: &mut Context,
+    trk: &mut SubstTracker,
+    opts: &Options,
+    elab: impl FnOnce(&mut Context, &mut SubstTracker) -> Result<Elaborated, SError>,
+    f: impl FnOnce(&mut Context, Expr, &mut SubstTracker) -> Result<(), SError>,
+) -> Result<(), SError> {
+    let obligations = ctx.transaction(|ctx| {
+        let el = elab(ctx, trk)?;
+        f(ctx, el.expr, trk)?;
+        Ok(el.obligations)
+    })?;
+    for (o, typ) in obligations {
+        println!("theorem {} : {}", o, pretty(ctx, &typ, opts));
+    }
+    Ok(())
+}
+
+fn process_record(rec: Record, ctx: &mut Context, trk: &mut SubstTracker) -> Result<(), SError> {
+    ctx.add_record(rec, trk)
+}
+
+fn process_class(rec: Record, ctx: &mut Context, trk: &mut SubstTracker) -> Result<(), SError> {
+    ctx.add_class(rec, trk)
+}
+
+fn process_instance(
+    inst: Instance,
+    ctx: &mut Context,
+    trk: &mut SubstTracker,
+    opts: &Options,
+) -> Result<(), SError> {
+    let name = inst.name.clone();
+    elaborated(
+        ctx,
+        trk,
+        opts,
+        |ctx, trk| ctx.elaborate_type(&name, &inst.typ, trk),
+        |ctx, typ, trk| {
+            let value = ctx.elaborate(&inst.name, &inst.value, Some(&typ), trk)?;
+            ctx.add_instance(inst.name, typ, value.expr, trk)?;
+            for (o, otyp) in value.obligations {
+                println!("theorem {} : {}", o, pretty(ctx, &otyp, opts));
+            }
+            Ok(())
+        },
+    )
+}
+
+fn process_def(
+    def: Def,
+    ctx: &mut Context,
+    trk: &mut SubstTracker,
+    opts: &Options,
+) -> Result<(), SError> {
+    let name = def.name.clone();
+    // The declared type if any, otherwise the value is elaborated first and
+    // its type is inferred.
+    let typ = def.typ;
+    elaborated(
+        ctx,
+        trk,
+        opts,
+        |ctx, trk| match &typ {
+            Some(typ) => ctx.elaborate_type(&name, typ, trk),
+            None => ctx.elaborate(&name, &def.value, None, trk),
+        },
+        |ctx, elaborated, trk| match typ {
+            Some(_) => {
+                let value = ctx.elaborate(&def.name, &def.value, Some(&elaborated), trk)?;
+                ctx.add_def(def.name, elaborated, value.expr, def.reducibility, trk)?;
+                for (o, otyp) in value.obligations {
+                    println!("theorem {} : {}", o, pretty(ctx, &otyp, opts));
+                }
+                Ok(())
+            }
+            None => {
+                let typ = ctx.add_def_inferred(def.name, elaborated, def.reducibility, trk)?;
+                println!("{} : {}", name, pretty(ctx, &typ, opts));
+                Ok(())
+            }
+        },
+    )
+}
+
+fn process_attribute(attr: Attribute, ctx: &mut Context) -> Result<(), SError> {
+    ctx.set_reducibility(&attr.name, attr.reducibility)
+}
+
+fn process_coercion(v: Variable, ctx: &mut Context, trk: &mut SubstTracker) -> Result<(), SError> {
+    ctx.add_coercion(&v, trk)
+}
+
+fn process_tactic(
+    tp: TacticProof,
+    ctx: &mut Context,
+    trk: &mut SubstTracker,
+    opts: &Options,
+) -> Result<(), SError> {
+    let pf = ctx.prove_by(&tp.name, &tp.tactic, trk)?;
+    println!("{} = {}", tp.name, pretty(ctx, &pf, opts));
+    Ok(())
 }
 
 fn display(e: SError) {
@@ -73,5 +553,131 @@ fn display(e: SError) {
         SError::ExprDoesNotTypeCheck(_) => println!("Expression does not type check"),
         SError::TypesDoesNotMatch(_) => println!("Types do not match"),
         SError::AlreadyExists => println!("Axiom or theorem with the same name already exists"),
+        SError::CannotDecide(_) => println!("Decision procedure does not evaluate to true"),
+        SError::ProofNotFound => println!("No proof found"),
+        SError::NotAClass(typ) => println!("{} is not an instance of a class", typ),
+        SError::InstanceNotFound(goal) => println!("No instance found for {}", goal),
+        SError::NotACoercion(typ) => println!("{} has no argument to coerce from", typ),
+        SError::CircularProof(v) => println!("Circular proof through {}", v),
+        SError::NotAType(e) => println!("{} is not a type", e),
+        SError::NoValue(v) => println!("{} has no value to unfold", v),
+        SError::AlreadyProven => {
+            println!("Theorem is already proven, use reprove to replace its proof")
+        }
+        SError::BrokenDependents(vs) => {
+            let vs: Vec<String> = vs.iter().map(|v| v.to_string()).collect();
+            println!("The new proof breaks {}", vs.join(", "));
+        }
+        SError::NotATheorem(v) => println!("{} is not a theorem and cannot be proven", v),
+        SError::SameFields(v) => println!("{} already has the same fields", v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Processes `text` line by line in a new context, every line has to
+    /// succeed.
+    fn replay(text: &str) -> Context {
+        let mut ctx = Context::new();
+        let mut trk = SubstTracker::new();
+        let mut src = Source::default();
+        let mut opts = Options::default();
+        for line in text.lines() {
+            let ok = process(line.to_owned(), &mut ctx, &mut trk, &mut src, &mut opts);
+            assert!(ok, "{} failed", line);
+        }
+        ctx
+    }
+
+    fn assert_round_trips(text: &str) {
+        let saved = replay(text).serialize();
+        assert_eq!(replay(&saved).serialize(), saved);
+    }
+
+    #[test]
+    fn saved_declarations_load_back() {
+        assert_round_trips(
+            "axiom Nat : type 0
+/// The zero
+/// natural.
+axiom zero : Nat
+axiom succ : forall (n : Nat), Nat
+class Add (A : type 0) : type 0 { add : forall (a : A), forall (b : A), A }
+instance addNat : (Add Nat) = { add := lambda (a : Nat), lambda (b : Nat), a }
+irreducible def two : Nat = (succ (succ zero))
+theorem t : Nat := two
+attribute reducible t
+theorem u : Nat
+record Point : type 0 { x : Nat, y : Nat }
+axiom f : forall (p : Point), Nat
+coercion f",
+        );
+    }
+
+    #[test]
+    fn proofs_are_saved_after_what_they_use() {
+        let saved = replay(
+            "axiom A : type 0
+theorem t : A
+axiom a : A
+t = a",
+        )
+        .serialize();
+        assert_eq!(
+            saved,
+            "axiom A : type 0\ntheorem t : A\naxiom a : A\nt = a\n"
+        );
+        assert_round_trips(&saved);
+    }
+
+    /// Writes `text` to a file of the temporary directory and returns its path.
+    fn temp_file(name: &str, text: &str) -> String {
+        let file = format!("sinepia_{}_{}", std::process::id(), name);
+        let path = std::env::temp_dir().join(file);
+        std::fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn failing_nested_loads_fail_the_outer_file() {
+        let inner = temp_file("inner.sp", "axiom A : type 0\naxiom A : type 0\n");
+        let outer = format!("axiom B : type 0\n:load {}\naxiom C : type 0\n", inner);
+        let outer = temp_file("outer.sp", &outer);
+        let mut ctx = Context::new();
+        let mut trk = SubstTracker::new();
+        let mut src = Source::default();
+        let mut opts = Options::default();
+        let load = format!(":load {}", outer);
+        assert!(!process(load, &mut ctx, &mut trk, &mut src, &mut opts));
+        assert_eq!(ctx.declarations().count(), 0);
+        for path in [inner, outer] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn failing_commands_are_reported() {
+        let mut ctx = replay("axiom A : type 0");
+        let mut trk = SubstTracker::new();
+        let mut src = Source::default();
+        let mut opts = Options::default();
+        for cmd in [
+            ":nope",
+            ":deps missing",
+            ":print axioms missing",
+            ":coercions maybe",
+        ] {
+            let ok = process(cmd.to_owned(), &mut ctx, &mut trk, &mut src, &mut opts);
+            assert!(!ok, "{} succeeded", cmd);
+        }
+        assert!(process(
+            ":deps A".to_owned(),
+            &mut ctx,
+            &mut trk,
+            &mut src,
+            &mut opts
+        ));
     }
 }